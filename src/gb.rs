@@ -3,21 +3,25 @@ use crate::{
     gb::hardware::{
         HardwareInit,
         audio::Audio,
-        cartridge::{Cartridge, load_cart},
+        cartridge::{
+            Cartridge, CartridgeError, RtcMode, camera::CameraImage, load_cart, tilt::TiltScript,
+            write_save,
+        },
         graphics::Graphics,
         input::Input,
-        memory::Memory,
+        memory::{Memory, dma::Dma},
         processor::Processor,
         serial::Serial,
         timer::Timer,
     },
-    has_opt, number_type,
-    options::{DO_BOOT, META_INST},
+    get_opt, get_opts, has_opt, number_type,
+    options::{CAMERA_IMAGE, DO_BOOT, META_INST, PATCH, ROM_ENTRY, RTC_FREEZE, SAVE_FILE, TILT},
 };
 use getopts::Matches;
 use log::info;
+use std::path::{Path, PathBuf};
 
-mod hardware;
+pub mod hardware;
 mod macros;
 mod regions;
 mod registers;
@@ -33,29 +37,62 @@ pub struct GameBoy {
     serial: Serial,
 
     opts: Matches,
+    save_path: PathBuf,
+    save_timer: u32,
+    tilt: Option<TiltScript>,
 
     exit: bool,
     meta_inst: bool,
     skip_boot: bool,
 }
 
-number_type!(MTime: u16);
-number_type!(Dot: u16);
+number_type!(pub MTime: u16);
+number_type!(pub Dot: u16);
+
+// At single speed, the PPU's dot clock runs four times as fast as the M-cycle clock
+const DOTS_PER_MTIME: u16 = 4;
+
+impl From<MTime> for Dot {
+    fn from(time: MTime) -> Self {
+        Dot(time.0 * DOTS_PER_MTIME)
+    }
+}
+
+// How often the battery save is flushed to disk if RAM or a clock has changed (~1 second)
+const SAVE_FLUSH_INTERVAL: u32 = 1_048_576;
 
 impl GameBoy {
-    pub fn new(opts: Matches) -> Self {
+    pub fn new(opts: Matches) -> Result<Self, CartridgeError> {
         // Make sure a ROM file is provided
         if opts.free.len() < 1 {
             error_panic!("No ROM file provided.");
         }
 
+        // Battery saves go next to the ROM unless told otherwise
+        let save_path = match get_opt!(opts, SAVE_FILE) {
+            Some(path) => PathBuf::from(path),
+            None => Path::new(&opts.free[0]).with_extension("sav"),
+        };
+
+        let rtc_mode = if has_opt!(opts, RTC_FREEZE) {
+            RtcMode::Frozen
+        } else {
+            RtcMode::CatchUp
+        };
+
         // Make gb
         let mut gb = Self {
             skip_boot: !has_opt!(opts, DO_BOOT),
             meta_inst: has_opt!(opts, META_INST),
             exit: false,
 
-            cart: load_cart(&opts.free[0]),
+            cart: load_cart(
+                &opts.free[0],
+                get_opt!(opts, ROM_ENTRY).as_deref(),
+                &get_opts!(opts, PATCH),
+                &save_path,
+                rtc_mode,
+            )?,
             cpu: Processor::default(),
             mem: Memory::default(),
             gfx: Graphics::default(),
@@ -65,8 +102,19 @@ impl GameBoy {
             serial: Serial::default(),
 
             opts,
+            save_path,
+            save_timer: 0,
+            tilt: None,
         };
 
+        if let Some(path) = get_opt!(gb.opts, CAMERA_IMAGE) {
+            let image = CameraImage::load(Path::new(&path))?;
+            gb.cart.set_camera_image(image);
+        }
+        if let Some(value) = get_opt!(gb.opts, TILT) {
+            gb.tilt = Some(TiltScript::load(&value)?);
+        }
+
         // Initialize
         gb.cart.init();
         Processor::init(&mut gb);
@@ -76,20 +124,75 @@ impl GameBoy {
         Input::init(&mut gb);
         Audio::init(&mut gb);
         Serial::init(&mut gb);
+        gb.feed_tilt();
 
-        gb
+        Ok(gb)
     }
 
     pub fn run(&mut self) {
         while !self.exit {
-            let time = Processor::step(self);
-            //TODO: run: update everything else
+            self.step();
+            if self.frame_completed() {
+                self.feed_tilt();
+            }
         }
 
         info!("Main loop ended. Shutting down.");
+        write_save(self.cart.as_ref(), &self.save_path);
+    }
+
+    /// Runs a single instruction and everything that happens alongside it. Headless tools can
+    /// call this until `frame_completed` returns true instead of using `run`.
+    pub fn step(&mut self) {
+        let time = Processor::step(self);
+        Dma::step(self, time);
+        self.cart.step(time);
+        Graphics::step(self, time.into());
+        self.maybe_flush_save(time);
+        //TODO: run: update everything else
+    }
+
+    fn maybe_flush_save(&mut self, time: MTime) {
+        self.save_timer += time.0 as u32;
+        if self.save_timer >= SAVE_FLUSH_INTERVAL {
+            self.save_timer = 0;
+            if self.cart.save_changed() {
+                write_save(self.cart.as_ref(), &self.save_path);
+            }
+        }
     }
 
     pub fn stop(&mut self) {
         self.exit = true;
     }
+
+    /// Returns the new state of the cartridge's rumble motor if it has changed since the last
+    /// call, so a frontend can start or stop its own feedback.
+    pub fn rumble_changed(&mut self) -> Option<bool> {
+        self.cart.rumble_changed()
+    }
+
+    /// Tilts the cartridge, for carts with an accelerometer. `x` and `y` are in g, so a
+    /// frontend can feed them from a motion sensor, a keyboard mapping or a script.
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.cart.set_tilt(x, y);
+    }
+
+    /// Moves the tilt script on to its next reading, if there is one.
+    fn feed_tilt(&mut self) {
+        if let Some(tilt) = &mut self.tilt {
+            let (x, y) = tilt.next_reading();
+            self.cart.set_tilt(x, y);
+        }
+    }
+
+    /// The last complete frame: 160x144 shades, row by row, 0 being the lightest.
+    pub fn frame(&self) -> &[u8] {
+        self.gfx.completed_frame().pixels()
+    }
+
+    /// Returns true once for each frame the PPU finishes, when `frame` has just been updated.
+    pub fn frame_completed(&mut self) -> bool {
+        self.gfx.take_frame_completed()
+    }
 }
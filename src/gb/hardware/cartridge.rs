@@ -1,15 +1,54 @@
 use crate::{
-    byte_fmt, error_panic,
-    gb::{hardware::cartridge::cartridge_romonly::CartRomOnly, regions::MemoryRegion},
-    unwrap_or_log,
+    byte_fmt,
+    gb::{
+        MTime,
+        hardware::cartridge::{
+            camera::CameraImage,
+            cartridge_huc1::CartHuc1,
+            cartridge_huc3::CartHuc3,
+            cartridge_mbc1::{CartMbc1, Mbc1Wiring},
+            cartridge_mbc2::CartMbc2,
+            cartridge_mbc3::CartMbc3,
+            cartridge_mbc5::CartMbc5,
+            cartridge_mbc6::CartMbc6,
+            cartridge_mbc7::CartMbc7,
+            cartridge_mmm01::CartMmm01,
+            cartridge_pocket_camera::CartPocketCamera,
+            cartridge_romonly::CartRomOnly,
+            cartridge_tama5::CartTama5,
+            header::CartridgeHeader,
+        },
+        regions::MemoryRegion,
+    },
 };
+use log::{debug, info, warn};
 use std::{
-    fs::File,
-    io::{Read, Seek, SeekFrom},
+    fmt, fs,
+    io::{self, ErrorKind},
     path::Path,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
+pub mod archive;
+pub mod camera;
+pub mod cartridge_huc1;
+pub mod cartridge_huc3;
+pub mod cartridge_mbc1;
+pub mod cartridge_mbc2;
+pub mod cartridge_mbc3;
+pub mod cartridge_mbc5;
+pub mod cartridge_mbc6;
+pub mod cartridge_mbc7;
+pub mod cartridge_mmm01;
+pub mod cartridge_pocket_camera;
 pub mod cartridge_romonly;
+pub mod cartridge_tama5;
+pub mod flash;
+pub mod header;
+pub mod infrared;
+pub mod patch;
+pub mod rom;
+pub mod tilt;
 
 pub const CART_ENTRY: u16 = 0x0100;
 pub const HEADER_LOGO: MemoryRegion = MemoryRegion::new(0x0104, 0x0133);
@@ -20,74 +59,346 @@ pub const HEADER_RAM_SIZE: u16 = 0x0149;
 pub const HEADER_CHECKSUM: u16 = 0x014D;
 pub const HEADER_GLOBAL_CHECKSUM: MemoryRegion = MemoryRegion::new(0x014E, 0x014F);
 
+pub const NINTENDO_LOGO: [u8; HEADER_LOGO.usize()] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+// The biggest ROM a header can declare (8 MiB)
+pub const MAX_ROM_SIZE: usize = 512 * ROM_BANK_SIZE;
+const MBC1M_ROM_BANKS: usize = 64;
+const MBC1M_GAME_BANKS: usize = 16;
+
+// Cart clocks run off their own 32.768 KHz crystal, but that divides evenly into the CPU clock
+const MTIME_PER_SECOND: u32 = 1_048_576;
+
+pub const ROM_BANK_0: MemoryRegion = MemoryRegion::new(0x0000, 0x3FFF);
+pub const ROM_BANK_N: MemoryRegion = MemoryRegion::new(0x4000, 0x7FFF);
+
+/// How a cart's real-time clock treats the time that passed while the emulator wasn't running.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RtcMode {
+    /// Advance the clock by the wall time since the save was written, like real hardware.
+    #[default]
+    CatchUp,
+    /// Restore the clock exactly as it was saved, so runs are deterministic.
+    Frozen,
+}
+
+/// Why a cart couldn't be loaded.
+#[derive(Debug)]
+pub enum CartridgeError {
+    /// The cart type byte in the header isn't a mapper we emulate.
+    UnsupportedMapper(u8),
+    /// The ROM size byte in the header isn't a known size.
+    BadRomSize(u8),
+    /// The RAM size byte in the header isn't a known size.
+    BadRamSize(u8),
+    /// The ROM file is bigger or smaller than it should be.
+    SizeMismatch { expected: usize, actual: usize },
+    /// A patch couldn't be applied.
+    BadPatch(String),
+    /// Reading the ROM, an archive or a patch failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedMapper(cart_type) => {
+                write!(f, "Unsupported cart type: {}", byte_fmt!(cart_type))
+            }
+            Self::BadRomSize(code) => write!(f, "Unsupported cart ROM size: {}", byte_fmt!(code)),
+            Self::BadRamSize(code) => write!(f, "Unsupported cart RAM size: {}", byte_fmt!(code)),
+            Self::SizeMismatch { expected, actual } => write!(
+                f,
+                "Cartridge file is {actual} bytes, but should be {expected} bytes"
+            ),
+            Self::BadPatch(reason) => write!(f, "Couldn't apply patch: {reason}"),
+            Self::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {}
+
+impl From<io::Error> for CartridgeError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
 pub trait Cartridge {
     fn init(&mut self);
 
+    /// Advances anything on the cart that runs on its own, like a real-time clock.
+    fn step(&mut self, time: MTime) {
+        // Most carts have nothing to update
+    }
+
     fn read_rom(&self, address: u16) -> u8;
     fn write_rom(&mut self, address: u16, value: u8);
     fn read_ram(&self, address: u16) -> u8;
     fn write_ram(&mut self, address: u16, value: u8);
 
-    fn load_from_file(&mut self, cart_file: &File);
+    /// Returns the new state of the rumble motor if it has changed since the last call.
+    fn rumble_changed(&mut self) -> Option<bool> {
+        // Most carts have no rumble motor
+        None
+    }
+
+    /// Feeds the cart's accelerometer, in g along each axis (0 is lying flat).
+    fn set_tilt(&mut self, x: f32, y: f32) {
+        // Most carts have no accelerometer
+    }
+
+    /// Replaces what the cart's image sensor sees.
+    fn set_camera_image(&mut self, image: CameraImage) {
+        // Most carts have no camera
+    }
+
+    /// Whether anything on the cart (usually its RAM) is kept alive by a battery.
+    fn has_battery(&self) -> bool {
+        false
+    }
+
+    /// The battery-backed data, as a raw dump in the same format other emulators use.
+    fn save_data(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn load_save_data(&mut self, data: &[u8], rtc_mode: RtcMode) {
+        // Nothing to load into by default
+    }
+
+    /// Returns true if the battery-backed data has changed since the last call.
+    fn save_changed(&mut self) -> bool {
+        false
+    }
+
+    fn load_from_bytes(&mut self, rom: &[u8]) -> Result<(), CartridgeError>;
+}
+
+/// Loads a cart from a ROM file, which may be inside a zip or gzip archive. `archive_entry`
+/// picks which file to use from a zip archive holding more than one ROM. Patches are applied
+/// before the header is read, so they can change the mapper or sizes.
+pub fn load_cart(
+    cart_path: &str,
+    archive_entry: Option<&str>,
+    patch_paths: &[String],
+    save_path: &Path,
+    rtc_mode: RtcMode,
+) -> Result<Box<dyn Cartridge>, CartridgeError> {
+    let cart_path = Path::new(cart_path);
+    let rom = archive::read_rom_file(cart_path, archive_entry)?;
+    let rom = patch::apply_patches(rom, cart_path, patch_paths)?;
+    log_header(&rom);
+    let mut cart = make_cart_from_rom(&rom)?;
+    load_save(cart.as_mut(), save_path, rtc_mode);
+    Ok(cart)
 }
 
-pub fn load_cart(cart_path: &str) -> Box<dyn Cartridge> {
-    let mut cart_file = unwrap_or_log!(File::open(Path::new(cart_path)));
-    let rom_info = get_rom_info(&mut cart_file);
-    let mut cart = make_cart_from_info(rom_info);
-    cart.load_from_file(&cart_file);
-    cart
+/// Makes the cart named by the ROM's header. An MMM01 menu's header wins over it, since the
+/// header at the start of those ROMs usually belongs to the first game.
+fn make_cart_from_rom(rom: &[u8]) -> Result<Box<dyn Cartridge>, CartridgeError> {
+    let mut cart = match mmm01_menu_info(rom) {
+        Some(menu_info) => make_cart_from_info(menu_info, Mbc1Wiring::Normal)?,
+        None => {
+            let rom_info = get_rom_info(rom)?;
+            let mbc1_wiring = if is_mbc1_multicart(rom, rom_info) {
+                Mbc1Wiring::Multicart
+            } else {
+                Mbc1Wiring::Normal
+            };
+            make_cart_from_info(rom_info, mbc1_wiring)?
+        }
+    };
+    cart.load_from_bytes(rom)?;
+    Ok(cart)
 }
 
-fn get_rom_info(cart_file: &mut File) -> (u8, u8, u8) {
-    let mut cart_info = [0; 3];
-    unwrap_or_log!(cart_file.seek(SeekFrom::Start(HEADER_CART_TYPE as u64)));
-    unwrap_or_log!(cart_file.read_exact(&mut cart_info));
-    unwrap_or_log!(cart_file.rewind());
-    (cart_info[0], cart_info[1], cart_info[2])
+fn load_save(cart: &mut dyn Cartridge, save_path: &Path, rtc_mode: RtcMode) {
+    if !cart.has_battery() {
+        return;
+    }
+
+    match fs::read(save_path) {
+        Ok(data) => {
+            info!("Loaded save file '{}'.", save_path.display());
+            cart.load_save_data(&data, rtc_mode);
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            info!("No save file at '{}'; starting fresh.", save_path.display())
+        }
+        Err(e) => warn!("Couldn't read save file '{}': {e}", save_path.display()),
+    }
+}
+
+pub fn write_save(cart: &dyn Cartridge, save_path: &Path) {
+    if !cart.has_battery() {
+        return;
+    }
+
+    match fs::write(save_path, cart.save_data()) {
+        Ok(()) => debug!("Wrote save file '{}'.", save_path.display()),
+        Err(e) => warn!("Couldn't write save file '{}': {e}", save_path.display()),
+    }
+}
+
+/// Seconds since the UNIX epoch, for timestamping cart clocks in save files.
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Copies a raw save dump into cart RAM. Mismatched sizes are tolerated, since different
+/// emulators disagree on how to pad saves for some carts.
+fn load_raw_ram(ram: &mut [u8], data: &[u8]) {
+    if data.len() != ram.len() {
+        warn!(
+            "Save data is {} bytes, but the cart has {} bytes of RAM.",
+            data.len(),
+            ram.len()
+        );
+    }
+    let len = data.len().min(ram.len());
+    ram[..len].copy_from_slice(&data[..len]);
+}
+
+/// Loads a save that may have a clock footer after the RAM dump. Returns the footer if it's
+/// one of the `footer_sizes` the cart knows how to read.
+fn load_ram_and_footer<'a>(
+    ram: &mut [u8],
+    data: &'a [u8],
+    footer_sizes: &[usize],
+) -> Option<&'a [u8]> {
+    let footer_size = data.len().saturating_sub(ram.len());
+    if footer_size == 0 {
+        load_raw_ram(ram, data);
+        return None;
+    }
+
+    let (ram_data, footer) = data.split_at(ram.len());
+    load_raw_ram(ram, ram_data);
+    if !footer_sizes.contains(&footer_size) {
+        warn!(
+            "Save file has a {footer_size} byte RTC footer, which isn't a known format; ignoring it."
+        );
+        return None;
+    }
+    Some(footer)
 }
 
-fn make_cart_from_info(rom_info: (u8, u8, u8)) -> Box<dyn Cartridge> {
+fn log_header(rom: &[u8]) {
+    let header = CartridgeHeader::parse(rom);
+    info!("Loading \"{}\" (version {})", header.title, header.version);
+    for warning in &header.warnings {
+        warn!("Cartridge header: {warning}");
+    }
+}
+
+fn get_rom_info(rom: &[u8]) -> Result<(u8, u8, u8), CartridgeError> {
+    let start = HEADER_CART_TYPE as usize;
+    match rom.get(start..start + 3) {
+        Some(info) => Ok((info[0], info[1], info[2])),
+        None => Err(CartridgeError::SizeMismatch {
+            expected: start + 3,
+            actual: rom.len(),
+        }),
+    }
+}
+
+/// MMM01 compilations boot into a menu in the last 32 KiB of ROM, and only the menu's header
+/// names the mapper; the header at the start usually belongs to the first game. The menu must
+/// have its own Nintendo logo, so stray bytes in other ROMs aren't taken for one.
+fn mmm01_menu_info(rom: &[u8]) -> Option<(u8, u8, u8)> {
+    let menu = rom.len().checked_sub(2 * ROM_BANK_SIZE)?;
+    let logo_start = menu + HEADER_LOGO.begin as usize;
+    if rom.get(logo_start..logo_start + HEADER_LOGO.usize()) != Some(&NINTENDO_LOGO) {
+        return None;
+    }
+    let rom_info = get_rom_info(&rom[menu..]).ok()?;
+    (0x0B..=0x0D).contains(&rom_info.0).then_some(rom_info)
+}
+
+/// MBC1M multicarts can't be told apart by their header, but each game in them is 256 KiB with
+/// its own header, so look for the Nintendo logo at the start of each of those sections.
+fn is_mbc1_multicart(rom: &[u8], rom_info: (u8, u8, u8)) -> bool {
+    let (cart_type, crom, _) = rom_info;
+    if !(0x01..=0x03).contains(&cart_type) || try_decode_rom_banks(crom) != Some(MBC1M_ROM_BANKS) {
+        return false;
+    }
+
+    let logo_count = (0..(MBC1M_ROM_BANKS / MBC1M_GAME_BANKS))
+        .filter(|game| {
+            let logo_start = game * MBC1M_GAME_BANKS * ROM_BANK_SIZE + HEADER_LOGO.begin as usize;
+            rom.get(logo_start..logo_start + HEADER_LOGO.usize()) == Some(&NINTENDO_LOGO)
+        })
+        .count();
+
+    // The menu plus at least one game
+    logo_count > 1
+}
+
+fn make_cart_from_info(
+    rom_info: (u8, u8, u8),
+    mbc1_wiring: Mbc1Wiring,
+) -> Result<Box<dyn Cartridge>, CartridgeError> {
     let (cart_type, crom, cram) = rom_info;
-    let rom_size = decode_rom_banks(crom);
-    let ram_size = decode_ram_size(cram);
+    let rom_size = decode_rom_banks(crom)?;
+    let ram_size = decode_ram_size(cram)?;
 
-    match cart_type {
+    Ok(match cart_type {
         // Note for the marked lines below (*):
         // MBC3 with 64 KiB of SRAM refers to MBC30, used only in Pocket Monsters: Crystal Version
         // (the Japanese version of Pokémon Crystal Version).
         0x00 => Box::new(CartRomOnly::default()), // ROM ONLY
-        //TODO: 0x01 => Box::new(/* todo */), // MBC1
-        //TODO: 0x02 => Box::new(/* todo */), // MBC1+RAM
-        //TODO: 0x03 => Box::new(/* todo */), // MBC1+RAM+BATTERY
-        //TODO: 0x05 => Box::new(/* todo */), // MBC2
-        //TODO: 0x06 => Box::new(/* todo */), // MBC2+BATTERY
-        //TODO: 0x0B => Box::new(/* todo */), // MMM01
-        //TODO: 0x0C => Box::new(/* todo */), // MMM01+RAM
-        //TODO: 0x0D => Box::new(/* todo */), // MMM01+RAM+BATTERY
-        //TODO: 0x0F => Box::new(/* todo */), // MBC3+TIMER+BATTERY
-        //TODO: 0x10 => Box::new(/* todo */), // MBC3+TIMER+RAM+BATTERY*
-        //TODO: 0x11 => Box::new(/* todo */), // MBC3
-        //TODO: 0x12 => Box::new(/* todo */), // MBC3+RAM*
-        //TODO: 0x13 => Box::new(/* todo */), // MBC3+RAM+BATTERY*
-        //TODO: 0x19 => Box::new(/* todo */), // MBC5
-        //TODO: 0x1A => Box::new(/* todo */), // MBC5+RAM
-        //TODO: 0x1B => Box::new(/* todo */), // MBC5+RAM+BATTERY
-        //TODO: 0x1C => Box::new(/* todo */), // MBC5+RUMBLE
-        //TODO: 0x1D => Box::new(/* todo */), // MBC5+RUMBLE+RAM
-        //TODO: 0x1E => Box::new(/* todo */), // MBC5+RUMBLE+RAM+BATTERY
-        //TODO: 0x20 => Box::new(/* todo */), // MBC6
-        //TODO: 0x22 => Box::new(/* todo */), // MBC7+SENSOR+RUMBLE+RAM+BATTERY
-        //TODO: 0xFC => Box::new(/* todo */), // POCKET CAMERA
-        //TODO: 0xFD => Box::new(/* todo */), // BANDAI TAMA5
-        //TODO: 0xFE => Box::new(/* todo */), // HuC3
-        //TODO: 0xFF => Box::new(/* todo */), // HuC1+RAM+BATTERY
-        _ => error_panic!("Unsupported cart type: {}", byte_fmt!(cart_type)),
-    }
-}
-
-fn decode_rom_banks(code: u8) -> usize {
-    match code {
+        0x01 => Box::new(CartMbc1::new(rom_size, 0, false, mbc1_wiring)), // MBC1
+        0x02 => Box::new(CartMbc1::new(rom_size, ram_size, false, mbc1_wiring)), // MBC1+RAM
+        0x03 => Box::new(CartMbc1::new(rom_size, ram_size, true, mbc1_wiring)), // MBC1+RAM+BATTERY
+        0x05 => Box::new(CartMbc2::new(rom_size, false)), // MBC2
+        0x06 => Box::new(CartMbc2::new(rom_size, true)), // MBC2+BATTERY
+        0x08 => Box::new(CartRomOnly::with_ram(false)), // ROM+RAM
+        0x09 => Box::new(CartRomOnly::with_ram(true)), // ROM+RAM+BATTERY
+        0x0B => Box::new(CartMmm01::new(rom_size, 0, false)), // MMM01
+        0x0C => Box::new(CartMmm01::new(rom_size, ram_size, false)), // MMM01+RAM
+        0x0D => Box::new(CartMmm01::new(rom_size, ram_size, true)), // MMM01+RAM+BATTERY
+        0x0F => Box::new(CartMbc3::new(rom_size, 0, true, true)), // MBC3+TIMER+BATTERY
+        0x10 => Box::new(CartMbc3::new(rom_size, ram_size, true, true)), // MBC3+TIMER+RAM+BATTERY*
+        0x11 => Box::new(CartMbc3::new(rom_size, 0, false, false)), // MBC3
+        0x12 => Box::new(CartMbc3::new(rom_size, ram_size, false, false)), // MBC3+RAM*
+        0x13 => Box::new(CartMbc3::new(rom_size, ram_size, true, false)), // MBC3+RAM+BATTERY*
+        0x19 => Box::new(CartMbc5::new(rom_size, 0, false, false)), // MBC5
+        0x1A => Box::new(CartMbc5::new(rom_size, ram_size, false, false)), // MBC5+RAM
+        0x1B => Box::new(CartMbc5::new(rom_size, ram_size, true, false)), // MBC5+RAM+BATTERY
+        0x1C => Box::new(CartMbc5::new(rom_size, 0, false, true)), // MBC5+RUMBLE
+        0x1D => Box::new(CartMbc5::new(rom_size, ram_size, false, true)), // MBC5+RUMBLE+RAM
+        0x1E => Box::new(CartMbc5::new(rom_size, ram_size, true, true)), // MBC5+RUMBLE+RAM+BATTERY
+        0x20 => Box::new(CartMbc6::new(rom_size, ram_size)), // MBC6
+        0x22 => Box::new(CartMbc7::new(rom_size)), // MBC7+SENSOR+RUMBLE+RAM+BATTERY
+        0xFC => Box::new(CartPocketCamera::new(rom_size)), // POCKET CAMERA
+        0xFD => Box::new(CartTama5::new(rom_size)), // BANDAI TAMA5
+        0xFE => Box::new(CartHuc3::new(rom_size, ram_size)), // HuC3
+        0xFF => Box::new(CartHuc1::new(rom_size, ram_size)), // HuC1+RAM+BATTERY
+        _ => return Err(CartridgeError::UnsupportedMapper(cart_type)),
+    })
+}
+
+fn decode_rom_banks(code: u8) -> Result<usize, CartridgeError> {
+    try_decode_rom_banks(code).ok_or(CartridgeError::BadRomSize(code))
+}
+
+fn decode_ram_size(code: u8) -> Result<usize, CartridgeError> {
+    try_decode_ram_size(code).ok_or(CartridgeError::BadRamSize(code))
+}
+
+fn try_decode_rom_banks(code: u8) -> Option<usize> {
+    Some(match code {
         0x00 => 2,   // 2 banks (32 KiB)
         0x01 => 4,   // 4 banks (64 KiB)
         0x02 => 8,   // 8 banks (128 KiB)
@@ -98,20 +409,32 @@ fn decode_rom_banks(code: u8) -> usize {
         0x07 => 256, // 256 banks (4 MiB)
         0x08 => 512, // 512 banks (8 MiB)
 
-        _ => error_panic!("Unsupported cart ROM size: {}", byte_fmt!(code)),
-    }
+        _ => return None,
+    })
 }
 
-fn decode_ram_size(code: u8) -> usize {
-    match code {
+fn try_decode_ram_size(code: u8) -> Option<usize> {
+    Some(match code {
         0x00 => 0,          // None
         0x02 => 8 * 1024,   // 8kib
         0x03 => 32 * 1024,  // 32kib
         0x04 => 128 * 1024, // 128kib
         0x05 => 64 * 1024,  // 64kib
 
-        _ => error_panic!("Unsupported cart RAM size: {}", byte_fmt!(code)),
+        _ => return None,
+    })
+}
+
+/// Makes a ROM of `banks` banks of `bank_size` bytes, where the first two bytes of every bank
+/// are that bank's number (low byte first), so tests can tell which bank is mapped where.
+#[cfg(test)]
+pub fn bank_numbered_rom(bank_size: usize, banks: usize) -> Vec<u8> {
+    let mut rom = vec![0; bank_size * banks];
+    for bank in 0..banks {
+        rom[bank * bank_size] = bank as u8;
+        rom[bank * bank_size + 1] = (bank >> 8) as u8;
     }
+    rom
 }
 
 #[cfg(test)]
@@ -119,16 +442,115 @@ mod tests {
     use super::*;
     use test_log::test;
 
-    fn file(path: &str) -> File {
-        File::open(Path::new(path)).unwrap()
-    }
-
     #[test]
     fn test_get_rom_info() {
-        let mut f = file("res/dummy_cartromonly_read_test.bin");
-        let (ct, rom, ram) = get_rom_info(&mut f);
+        let f = fs::read("res/dummy_cartromonly_read_test.bin").unwrap();
+        let (ct, rom, ram) = get_rom_info(&f).unwrap();
         assert_eq!(ct, 0x11);
         assert_eq!(rom, 0x22);
         assert_eq!(ram, 0x33);
     }
+
+    #[test]
+    fn test_mmm01_menu_info() {
+        let mut rom = vec![0; 8 * ROM_BANK_SIZE];
+        rom[HEADER_CART_TYPE as usize] = 0x01;
+        assert_eq!(mmm01_menu_info(&rom), None);
+
+        let menu = 6 * ROM_BANK_SIZE + HEADER_CART_TYPE as usize;
+        rom[menu..menu + 3].copy_from_slice(&[0x0D, 0x02, 0x03]);
+        // Not without the menu's logo
+        assert_eq!(mmm01_menu_info(&rom), None);
+
+        let logo = 6 * ROM_BANK_SIZE + HEADER_LOGO.begin as usize;
+        rom[logo..logo + HEADER_LOGO.usize()].copy_from_slice(&NINTENDO_LOGO);
+        assert_eq!(mmm01_menu_info(&rom), Some((0x0D, 0x02, 0x03)));
+        assert_eq!(mmm01_menu_info(&rom[..0x100]), None);
+    }
+
+    #[test]
+    fn test_mmm01_fallback() {
+        // The first game's MBC5 header at the start, and the menu's MMM01 one in the last 32 KiB
+        let mut rom = vec![0; 8 * ROM_BANK_SIZE];
+        rom[HEADER_CART_TYPE as usize..HEADER_CART_TYPE as usize + 3]
+            .copy_from_slice(&[0x19, 0x02, 0x00]);
+        let menu = rom.len() - 2 * ROM_BANK_SIZE;
+        rom[menu + HEADER_CART_TYPE as usize..menu + HEADER_CART_TYPE as usize + 3]
+            .copy_from_slice(&[0x0B, 0x02, 0x00]);
+        rom[0] = 0xAA;
+        rom[menu] = 0xBB;
+
+        // Without the menu's logo it's just an MBC5, which starts with bank 0 at 0000
+        let cart = make_cart_from_rom(&rom).unwrap();
+        assert_eq!(cart.read_rom(0x0000), 0xAA);
+
+        // With it, the menu's header wins and the cart boots into the menu
+        let logo = menu + HEADER_LOGO.begin as usize;
+        rom[logo..logo + HEADER_LOGO.usize()].copy_from_slice(&NINTENDO_LOGO);
+        let mut cart = make_cart_from_rom(&rom).unwrap();
+        cart.init();
+        assert_eq!(cart.read_rom(0x0000), 0xBB);
+    }
+
+    #[test]
+    fn test_load_errors() {
+        assert!(matches!(
+            make_cart_from_info((0xEE, 0x00, 0x00), Mbc1Wiring::Normal),
+            Err(CartridgeError::UnsupportedMapper(0xEE))
+        ));
+        assert!(matches!(
+            make_cart_from_info((0x01, 0x42, 0x00), Mbc1Wiring::Normal),
+            Err(CartridgeError::BadRomSize(0x42))
+        ));
+        assert!(matches!(
+            make_cart_from_info((0x01, 0x00, 0x42), Mbc1Wiring::Normal),
+            Err(CartridgeError::BadRamSize(0x42))
+        ));
+        assert!(matches!(
+            get_rom_info(&[0; 0x100]),
+            Err(CartridgeError::SizeMismatch { .. })
+        ));
+        assert!(matches!(
+            load_cart("res/missing.gb", None, &[], Path::new(""), RtcMode::Frozen),
+            Err(CartridgeError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_save_round_trip() {
+        let path = std::env::temp_dir().join("gbemu_test_save_round_trip.sav");
+        let mut cart = CartMbc5::new(2, 8 * 1024, true, false);
+        cart.init();
+        cart.write_rom(0x0000, 0x0A);
+        cart.write_ram(0xA123, 0x45);
+        assert!(cart.save_changed());
+        assert!(!cart.save_changed());
+        write_save(&cart, &path);
+
+        let mut loaded = CartMbc5::new(2, 8 * 1024, true, false);
+        loaded.init();
+        load_save(&mut loaded, &path, RtcMode::Frozen);
+        loaded.write_rom(0x0000, 0x0A);
+        assert_eq!(loaded.read_ram(0xA123), 0x45);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_is_mbc1_multicart() {
+        let mut rom = vec![0; MBC1M_ROM_BANKS * ROM_BANK_SIZE];
+        let rom_info = (0x01, 0x05, 0x00);
+
+        // One logo (a normal 1 MiB MBC1 cart)
+        rom[HEADER_LOGO.begin as usize..=HEADER_LOGO.end as usize].copy_from_slice(&NINTENDO_LOGO);
+        assert!(!is_mbc1_multicart(&rom, rom_info));
+
+        // A logo at the start of another 256 KiB game
+        let game_1 = MBC1M_GAME_BANKS * ROM_BANK_SIZE + HEADER_LOGO.begin as usize;
+        rom[game_1..game_1 + HEADER_LOGO.usize()].copy_from_slice(&NINTENDO_LOGO);
+        assert!(is_mbc1_multicart(&rom, rom_info));
+
+        // Not an MBC1 cart at all
+        assert!(!is_mbc1_multicart(&rom, (0x00, 0x05, 0x00)));
+    }
 }
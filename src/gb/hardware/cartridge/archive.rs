@@ -0,0 +1,153 @@
+use crate::gb::hardware::cartridge::{CartridgeError, MAX_ROM_SIZE};
+use flate2::read::GzDecoder;
+use log::info;
+use std::{
+    fs,
+    io::{self, Cursor, Read},
+    path::Path,
+};
+use zip::ZipArchive;
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+const ROM_EXTENSIONS: &[&str] = &["gb", "gbc"];
+
+/// Reads a ROM file into memory, transparently decompressing it if it's a zip or gzip archive.
+/// For zip archives, `entry` picks a file by name; otherwise the first ROM file in it is used.
+pub fn read_rom_file(path: &Path, entry: Option<&str>) -> Result<Vec<u8>, CartridgeError> {
+    let data = fs::read(path)?;
+    unpack_rom(data, entry)
+}
+
+fn unpack_rom(data: Vec<u8>, entry: Option<&str>) -> Result<Vec<u8>, CartridgeError> {
+    if data.starts_with(ZIP_MAGIC) {
+        read_zip_entry(data, entry)
+    } else if data.starts_with(GZIP_MAGIC) {
+        info!("Decompressing gzip ROM.");
+        read_limited(GzDecoder::new(data.as_slice()))
+    } else {
+        Ok(data)
+    }
+}
+
+fn read_zip_entry(data: Vec<u8>, entry: Option<&str>) -> Result<Vec<u8>, CartridgeError> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(io::Error::from)?;
+
+    let name = match entry {
+        Some(name) => name.to_string(),
+        None => {
+            // file_names() isn't in archive order, so go by index to find the first ROM
+            let first_rom = (0..archive.len())
+                .filter_map(|i| archive.name_for_index(i))
+                .find(|name| is_rom_name(name));
+            match first_rom {
+                Some(name) => name.to_string(),
+                None => {
+                    return Err(CartridgeError::Io(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        "No .gb or .gbc file found in zip archive.",
+                    )));
+                }
+            }
+        }
+    };
+
+    info!("Loading '{name}' from zip archive.");
+    let file = archive.by_name(&name).map_err(io::Error::from)?;
+    read_limited(file)
+}
+
+/// Decompresses a ROM, giving up once it's bigger than any cart could be rather than filling
+/// memory with whatever a broken or malicious archive expands to.
+fn read_limited(reader: impl Read) -> Result<Vec<u8>, CartridgeError> {
+    let mut rom = Vec::new();
+    reader.take(MAX_ROM_SIZE as u64 + 1).read_to_end(&mut rom)?;
+    if rom.len() > MAX_ROM_SIZE {
+        return Err(CartridgeError::Io(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Decompressed ROM is bigger than any cartridge.",
+        )));
+    }
+    Ok(rom)
+}
+
+fn is_rom_name(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ROM_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compression, write::GzEncoder};
+    use std::io::Write;
+    use test_log::test;
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
+    fn make_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_raw() {
+        assert_eq!(unpack_rom(vec![1, 2, 3], None).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_gzip() {
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&[0xAA; 100]).unwrap();
+        let data = gz.finish().unwrap();
+        assert_eq!(unpack_rom(data, None).unwrap(), vec![0xAA; 100]);
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&vec![0; MAX_ROM_SIZE]).unwrap();
+        let data = gz.finish().unwrap();
+        assert_eq!(unpack_rom(data, None).unwrap().len(), MAX_ROM_SIZE);
+
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&vec![0; MAX_ROM_SIZE + 1]).unwrap();
+        let data = gz.finish().unwrap();
+        assert!(matches!(unpack_rom(data, None), Err(CartridgeError::Io(_))));
+
+        let zip = make_zip(&[("big.gb", &vec![0; MAX_ROM_SIZE + 1])]);
+        assert!(matches!(unpack_rom(zip, None), Err(CartridgeError::Io(_))));
+    }
+
+    #[test]
+    fn test_zip() {
+        let zip = make_zip(&[
+            ("readme.txt", b"hello"),
+            ("game.GB", &[1, 2, 3]),
+            ("other.gbc", &[4, 5, 6]),
+        ]);
+
+        // First ROM in the archive by default
+        assert_eq!(unpack_rom(zip.clone(), None).unwrap(), vec![1, 2, 3]);
+
+        // Or a specific one
+        assert_eq!(
+            unpack_rom(zip.clone(), Some("other.gbc")).unwrap(),
+            vec![4, 5, 6]
+        );
+        assert_eq!(
+            unpack_rom(zip.clone(), Some("readme.txt")).unwrap(),
+            b"hello".to_vec()
+        );
+
+        // Or nothing at all
+        let no_roms = make_zip(&[("readme.txt", b"hello")]);
+        assert!(unpack_rom(no_roms, None).is_err());
+        assert!(unpack_rom(zip, Some("missing.gb")).is_err());
+    }
+}
@@ -0,0 +1,326 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+/// The part of the sensor the camera software reads out.
+pub const SENSOR_WIDTH: usize = 128;
+pub const SENSOR_HEIGHT: usize = 112;
+
+// Where the processed picture lands in RAM bank 0, as 16x14 tiles in the usual 2bpp format
+pub const IMAGE_OFFSET: usize = 0x0100;
+pub const IMAGE_SIZE: usize = SENSOR_WIDTH * SENSOR_HEIGHT / 4;
+
+pub const REGISTER_COUNT: usize = 0x36;
+
+const REG_CAPTURE: usize = 0x00;
+const REG_EDGE_AND_GAIN: usize = 0x01;
+const REG_EXPOSURE_HIGH: usize = 0x02;
+const REG_EXPOSURE_LOW: usize = 0x03;
+const REG_EDGE_RATIO_AND_INVERT: usize = 0x04;
+const REG_DITHER_MATRIX: usize = 0x06;
+
+const N_BIT: u8 = 0x80;
+const INVERT_BIT: u8 = 0x08;
+
+// Exposure is in steps of 16 M-cycles; this many steps gives the image back unscaled
+const NEUTRAL_EXPOSURE: f32 = 0x1000 as f32;
+const EDGE_RATIOS: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+
+/// What the sensor is pointed at: one brightness (0 is black) per pixel.
+#[derive(Debug, Clone)]
+pub struct CameraImage {
+    pixels: Vec<u8>,
+}
+
+impl CameraImage {
+    /// Grey bars getting darker from left to right, with a checkerboard along the bottom, so
+    /// exposure, edge and dithering changes are all easy to spot.
+    pub fn test_pattern() -> Self {
+        let mut pixels = vec![0; SENSOR_WIDTH * SENSOR_HEIGHT];
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            let (x, y) = (i % SENSOR_WIDTH, i / SENSOR_WIDTH);
+            *pixel = if y >= SENSOR_HEIGHT * 3 / 4 {
+                if (x / 8 + y / 8) % 2 == 0 { 0xFF } else { 0x00 }
+            } else {
+                (0xFF - x * 0xFF / (SENSOR_WIDTH - 1)) as u8
+            };
+        }
+        Self { pixels }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::from_pnm(&fs::read(path)?)
+    }
+
+    /// Reads a binary PGM (P5) or PPM (P6) image, scaling it to the sensor's size.
+    pub fn from_pnm(file: &[u8]) -> io::Result<Self> {
+        let mut reader = PnmReader { file, pos: 0 };
+        let channels = match reader.token()? {
+            b"P5" => 1,
+            b"P6" => 3,
+            _ => return Err(image_error("not a binary PGM or PPM image")),
+        };
+        let width = reader.number()?;
+        let height = reader.number()?;
+        let max_value = reader.number()?;
+        if width == 0 || height == 0 || !(1..=255).contains(&max_value) {
+            return Err(image_error("unsupported image size or depth"));
+        }
+
+        // A single whitespace byte separates the header from the samples
+        let start = reader.pos + 1;
+        let data = width
+            .checked_mul(height)
+            .and_then(|samples| samples.checked_mul(channels))
+            .and_then(|size| size.checked_add(start))
+            .and_then(|end| file.get(start..end))
+            .ok_or_else(|| image_error("image data is truncated"))?;
+
+        let mut pixels = vec![0; SENSOR_WIDTH * SENSOR_HEIGHT];
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            let x = (i % SENSOR_WIDTH) * width / SENSOR_WIDTH;
+            let y = (i / SENSOR_WIDTH) * height / SENSOR_HEIGHT;
+            let sample = &data[(y * width + x) * channels..][..channels];
+            let level = sample.iter().map(|&c| c as usize).sum::<usize>() / channels;
+            *pixel = (level * 0xFF / max_value) as u8;
+        }
+        Ok(Self { pixels })
+    }
+
+    fn brightness(&self, x: isize, y: isize) -> f32 {
+        // Pixels past the edge repeat the border
+        let x = x.clamp(0, SENSOR_WIDTH as isize - 1) as usize;
+        let y = y.clamp(0, SENSOR_HEIGHT as isize - 1) as usize;
+        self.pixels[y * SENSOR_WIDTH + x] as f32
+    }
+}
+
+impl Default for CameraImage {
+    fn default() -> Self {
+        Self::test_pattern()
+    }
+}
+
+struct PnmReader<'a> {
+    file: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PnmReader<'a> {
+    fn token(&mut self) -> io::Result<&'a [u8]> {
+        loop {
+            match self.file.get(self.pos) {
+                Some(b'#') => {
+                    while self.file.get(self.pos).is_some_and(|&c| c != b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                Some(c) if c.is_ascii_whitespace() => self.pos += 1,
+                Some(_) => break,
+                None => return Err(image_error("image header is truncated")),
+            }
+        }
+        let start = self.pos;
+        while self
+            .file
+            .get(self.pos)
+            .is_some_and(|c| !c.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+        Ok(&self.file[start..self.pos])
+    }
+
+    fn number(&mut self) -> io::Result<usize> {
+        std::str::from_utf8(self.token()?)
+            .ok()
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| image_error("bad number in image header"))
+    }
+}
+
+fn image_error(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("Camera image: {message}"))
+}
+
+/// How long a capture keeps the busy bit set, in M-cycles.
+pub fn capture_time(registers: &[u8; REGISTER_COUNT]) -> u32 {
+    let n_bonus = if registers[REG_EDGE_AND_GAIN] & N_BIT != 0 {
+        0
+    } else {
+        512
+    };
+    32_446 + n_bonus + 16 * exposure(registers) as u32
+}
+
+fn exposure(registers: &[u8; REGISTER_COUNT]) -> u16 {
+    u16::from_be_bytes([registers[REG_EXPOSURE_HIGH], registers[REG_EXPOSURE_LOW]])
+}
+
+/// Runs the M64282FP's processing over `image` and writes the result as tile data to `out`.
+///
+/// Exposure scales the brightness, edge enhancement adds the difference from the neighbours the
+/// VH bits select (only with the N bit set, which is how the camera software uses it), and each
+/// pixel is then compared against its three thresholds in the 4x4 dither matrix. The analog
+/// gain and reference voltages aren't modelled; the camera software compensates with exposure.
+pub fn capture(registers: &[u8; REGISTER_COUNT], image: &CameraImage, out: &mut [u8]) {
+    let edge_flags = registers[REG_EDGE_AND_GAIN];
+    let (horizontal, vertical) = if edge_flags & N_BIT != 0 {
+        (edge_flags & 0x20 != 0, edge_flags & 0x40 != 0)
+    } else {
+        (false, false)
+    };
+    let edge_ratio = EDGE_RATIOS[((registers[REG_EDGE_RATIO_AND_INVERT] >> 4) & 0x07) as usize];
+    let invert = registers[REG_EDGE_RATIO_AND_INVERT] & INVERT_BIT != 0;
+    let exposure = exposure(registers) as f32 / NEUTRAL_EXPOSURE;
+
+    let exposed = |x: isize, y: isize| image.brightness(x, y) * exposure;
+
+    out[..IMAGE_SIZE].fill(0);
+    for y in 0..SENSOR_HEIGHT {
+        for x in 0..SENSOR_WIDTH {
+            let (sx, sy) = (x as isize, y as isize);
+            let center = exposed(sx, sy);
+            let mut edge = 0.0;
+            if horizontal {
+                edge += 2.0 * center - exposed(sx - 1, sy) - exposed(sx + 1, sy);
+            }
+            if vertical {
+                edge += 2.0 * center - exposed(sx, sy - 1) - exposed(sx, sy + 1);
+            }
+
+            let mut level = (center + edge * edge_ratio).clamp(0.0, 255.0) as u8;
+            if invert {
+                level = 0xFF - level;
+            }
+
+            let thresholds = &registers[REG_DITHER_MATRIX + ((y & 3) * 4 + (x & 3)) * 3..][..3];
+            let shade = thresholds.iter().filter(|&&t| level < t).count() as u8;
+
+            let tile = (y / 8) * (SENSOR_WIDTH / 8) + x / 8;
+            let row = tile * 16 + (y % 8) * 2;
+            let bit = 0x80 >> (x % 8);
+            if shade & 0x01 != 0 {
+                out[row] |= bit;
+            }
+            if shade & 0x02 != 0 {
+                out[row + 1] |= bit;
+            }
+        }
+    }
+}
+
+/// Whether a capture has been started and not finished yet.
+pub fn capture_started(registers: &[u8; REGISTER_COUNT]) -> bool {
+    registers[REG_CAPTURE] & 0x01 != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    fn registers(exposure: u16, threshold: u8) -> [u8; REGISTER_COUNT] {
+        let mut registers = [0; REGISTER_COUNT];
+        registers[REG_EXPOSURE_HIGH..=REG_EXPOSURE_LOW].copy_from_slice(&exposure.to_be_bytes());
+        // Every pixel: black below `threshold`, white above it
+        for cell in registers[REG_DITHER_MATRIX..].chunks_mut(3) {
+            cell.fill(threshold);
+        }
+        registers
+    }
+
+    fn shade(out: &[u8], x: usize, y: usize) -> u8 {
+        let row = ((y / 8) * 16 + x / 8) * 16 + (y % 8) * 2;
+        let bit = 7 - x % 8;
+        ((out[row] >> bit) & 1) | (((out[row + 1] >> bit) & 1) << 1)
+    }
+
+    #[test]
+    fn test_pnm() {
+        let mut file = b"P5\n# comment\n2 1\n255\n".to_vec();
+        file.extend([0x00, 0xFF]);
+        let image = CameraImage::from_pnm(&file).unwrap();
+        assert_eq!(image.brightness(0, 0), 0.0);
+        assert_eq!(image.brightness(SENSOR_WIDTH as isize - 1, 50), 255.0);
+
+        let mut file = b"P6 1 1 15 ".to_vec();
+        file.extend([15, 15, 0]);
+        let image = CameraImage::from_pnm(&file).unwrap();
+        assert_eq!(image.brightness(0, 0), 170.0);
+
+        assert!(CameraImage::from_pnm(b"P5 2 2 255 \x00").is_err());
+        assert!(CameraImage::from_pnm(b"P2 1 1 255 0").is_err());
+        // Sizes too big to even multiply out
+        let huge = format!("P6 {} {} 255 ", usize::MAX / 2, usize::MAX / 2);
+        assert!(CameraImage::from_pnm(huge.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_exposure() {
+        let image = CameraImage::test_pattern();
+        let mut out = [0; IMAGE_SIZE];
+
+        // The leftmost bar is white and the rightmost black at neutral exposure
+        capture(&registers(0x1000, 0x80), &image, &mut out);
+        assert_eq!(shade(&out, 0, 0), 0);
+        assert_eq!(shade(&out, SENSOR_WIDTH - 1, 0), 3);
+
+        // No light at all makes everything black
+        capture(&registers(0, 0x80), &image, &mut out);
+        assert_eq!(shade(&out, 0, 0), 3);
+
+        let mut inverted = registers(0x1000, 0x80);
+        inverted[REG_EDGE_RATIO_AND_INVERT] = INVERT_BIT;
+        capture(&inverted, &image, &mut out);
+        assert_eq!(shade(&out, 0, 0), 3);
+    }
+
+    #[test]
+    fn test_dithering() {
+        let image = CameraImage::test_pattern();
+        let mut registers = registers(0x1000, 0);
+        // Thresholds for each of the four shades
+        registers[REG_DITHER_MATRIX..REG_DITHER_MATRIX + 3].copy_from_slice(&[0x40, 0x80, 0xC0]);
+        let mut out = [0; IMAGE_SIZE];
+        capture(&registers, &image, &mut out);
+
+        assert_eq!(shade(&out, 0, 0), 0);
+        assert_eq!(shade(&out, 32, 0), 1);
+        assert_eq!(shade(&out, 64, 0), 2);
+        assert_eq!(shade(&out, 124, 0), 3);
+        // Only the first cell of the matrix has thresholds
+        assert_eq!(shade(&out, 125, 0), 0);
+    }
+
+    #[test]
+    fn test_edge_enhancement() {
+        let image = CameraImage::test_pattern();
+        let mut registers = registers(0x1000, 0x80);
+        let mut out = [0; IMAGE_SIZE];
+        // The checkerboard's squares are 8 pixels wide: x=7 is white next to a black square
+        let (x, y) = (7, SENSOR_HEIGHT - 16);
+        registers[REG_EXPOSURE_HIGH..=REG_EXPOSURE_LOW].copy_from_slice(&0x0700u16.to_be_bytes());
+        capture(&registers, &image, &mut out);
+        assert_eq!(shade(&out, x, y), 3);
+
+        registers[REG_EDGE_AND_GAIN] = N_BIT | 0x60;
+        registers[REG_EDGE_RATIO_AND_INVERT] = 0x20;
+        capture(&registers, &image, &mut out);
+        assert_eq!(shade(&out, x, y), 0);
+        // Edge enhancement needs the N bit
+        registers[REG_EDGE_AND_GAIN] = 0x60;
+        capture(&registers, &image, &mut out);
+        assert_eq!(shade(&out, x, y), 3);
+    }
+
+    #[test]
+    fn test_capture_time() {
+        let mut registers = registers(0x0100, 0);
+        assert_eq!(capture_time(&registers), 32_446 + 512 + 0x1000);
+        registers[REG_EDGE_AND_GAIN] = N_BIT;
+        assert_eq!(capture_time(&registers), 32_446 + 0x1000);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{
+    gb::{
+        hardware::{
+            cartridge::{
+                Cartridge, CartridgeError, RAM_BANK_SIZE, ROM_BANK_0, ROM_BANK_N, RtcMode,
+                infrared::IrPort, load_raw_ram, rom::Rom,
+            },
+            memory::{OPEN_BUS_VALUE, UNINIT_VALUE},
+        },
+        regions::{CART_RAM, MemoryRegion, ROM_SPACE},
+    },
+    region_guard,
+};
+
+const IR_SELECT: MemoryRegion = MemoryRegion::new(0x0000, 0x1FFF);
+const ROM_BANK: MemoryRegion = MemoryRegion::new(0x2000, 0x3FFF);
+const RAM_BANK: MemoryRegion = MemoryRegion::new(0x4000, 0x5FFF);
+
+const IR_SELECT_VALUE: u8 = 0x0E;
+const ROM_BANK_MASK: u8 = 0x3F;
+const RAM_BANK_MASK: u8 = 0x03;
+
+#[derive(Debug)]
+pub struct CartHuc1 {
+    rom: Rom,
+    ram: Vec<u8>,
+    rom_banks: usize,
+    ram_dirty: bool,
+    ir: IrPort,
+
+    // Registers
+    ir_selected: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+}
+
+impl CartHuc1 {
+    /// HuC1 carts always have a battery.
+    pub fn new(rom_banks: usize, ram_size: usize) -> Self {
+        Self {
+            rom: Rom::default(),
+            ram: vec![UNINIT_VALUE; ram_size],
+            rom_banks,
+            ram_dirty: false,
+            ir: IrPort::default(),
+            ir_selected: false,
+            rom_bank: 1,
+            ram_bank: 0,
+        }
+    }
+
+    fn ram_offset(&self, address: u16) -> Option<usize> {
+        if self.ram.is_empty() {
+            return None;
+        }
+        let offset =
+            self.ram_bank as usize * RAM_BANK_SIZE + CART_RAM.local_address(address) as usize;
+        Some(offset % self.ram.len())
+    }
+}
+
+impl Cartridge for CartHuc1 {
+    fn init(&mut self) {
+        self.ir_selected = false;
+        self.rom_bank = 1;
+        self.ram_bank = 0;
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        region_guard!(address in ROM_SPACE);
+
+        if ROM_BANK_0.contains(address) {
+            self.rom.read(ROM_BANK_0.local_address(address) as usize)
+        } else {
+            self.rom
+                .read_bank(self.rom_bank as usize, ROM_BANK_N.local_address(address))
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        region_guard!(address in ROM_SPACE);
+
+        if IR_SELECT.contains(address) {
+            // There's no RAM enable; this swaps the RAM for the IR port instead
+            self.ir_selected = (value & 0x0F) == IR_SELECT_VALUE;
+        } else if ROM_BANK.contains(address) {
+            self.rom_bank = value & ROM_BANK_MASK;
+        } else if RAM_BANK.contains(address) {
+            self.ram_bank = value & RAM_BANK_MASK;
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        region_guard!(address in CART_RAM);
+
+        if self.ir_selected {
+            self.ir.read()
+        } else {
+            match self.ram_offset(address) {
+                Some(offset) => self.ram[offset],
+                None => OPEN_BUS_VALUE,
+            }
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        region_guard!(address in CART_RAM);
+
+        if self.ir_selected {
+            self.ir.write(value);
+        } else if let Some(offset) = self.ram_offset(address) {
+            self.ram[offset] = value;
+            self.ram_dirty = true;
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn load_save_data(&mut self, data: &[u8], rtc_mode: RtcMode) {
+        load_raw_ram(&mut self.ram, data);
+    }
+
+    fn save_changed(&mut self) -> bool {
+        std::mem::take(&mut self.ram_dirty)
+    }
+
+    fn load_from_bytes(&mut self, rom: &[u8]) -> Result<(), CartridgeError> {
+        self.rom = Rom::new(rom, self.rom_banks);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gb::hardware::cartridge::{ROM_BANK_SIZE, bank_numbered_rom};
+    use test_log::test;
+
+    /// Makes a cart where the first byte of every ROM bank is that bank's number.
+    fn make_cart(rom_banks: usize, ram_size: usize) -> CartHuc1 {
+        let mut cart = CartHuc1::new(rom_banks, ram_size);
+        cart.load_from_bytes(&bank_numbered_rom(ROM_BANK_SIZE, rom_banks))
+            .unwrap();
+        cart.init();
+        cart
+    }
+
+    #[test]
+    fn test_banking() {
+        let mut cart = make_cart(64, 32 * 1024);
+
+        cart.write_rom(0x2000, 0x3F);
+        assert_eq!(cart.read_rom(0x4000), 0x3F);
+        cart.write_rom(0x2000, 0x45);
+        assert_eq!(cart.read_rom(0x4000), 0x05);
+
+        // RAM works without being enabled first
+        for bank in 0..4 {
+            cart.write_rom(0x4000, bank);
+            cart.write_ram(0xA000, bank + 0x10);
+        }
+        for bank in 0..4 {
+            cart.write_rom(0x4000, bank);
+            assert_eq!(cart.read_ram(0xA000), bank + 0x10);
+        }
+        assert!(cart.save_changed());
+    }
+
+    #[test]
+    fn test_ir_select() {
+        let mut cart = make_cart(2, 8 * 1024);
+        cart.write_ram(0xA000, 0x42);
+
+        cart.write_rom(0x0000, 0x0E);
+        assert_eq!(cart.read_ram(0xA000), IrPort::default().read());
+        cart.write_ram(0xA000, 0x01);
+        assert!(cart.ir.led_on());
+
+        // The RAM wasn't touched by the IR write
+        cart.write_rom(0x0000, 0x00);
+        assert_eq!(cart.read_ram(0xA000), 0x42);
+    }
+}
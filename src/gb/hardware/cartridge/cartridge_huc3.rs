@@ -0,0 +1,433 @@
+use crate::{
+    byte_fmt,
+    gb::{
+        MTime,
+        hardware::{
+            cartridge::{
+                Cartridge, CartridgeError, MTIME_PER_SECOND, RAM_BANK_SIZE, ROM_BANK_0, ROM_BANK_N,
+                RtcMode, infrared::IrPort, load_ram_and_footer, rom::Rom, unix_time,
+            },
+            memory::{OPEN_BUS_VALUE, UNINIT_VALUE},
+        },
+        regions::{CART_RAM, MemoryRegion, ROM_SPACE},
+    },
+    region_guard,
+};
+use log::{debug, info};
+
+const MODE_SELECT: MemoryRegion = MemoryRegion::new(0x0000, 0x1FFF);
+const ROM_BANK: MemoryRegion = MemoryRegion::new(0x2000, 0x3FFF);
+const RAM_BANK: MemoryRegion = MemoryRegion::new(0x4000, 0x5FFF);
+
+const ROM_BANK_MASK: u8 = 0x7F;
+const RAM_BANK_MASK: u8 = 0x03;
+
+// What CART_RAM is connected to, selected by writing to MODE_SELECT
+const MODE_RAM_READ: u8 = 0x00;
+const MODE_RAM_WRITE: u8 = 0x0A;
+const MODE_COMMAND: u8 = 0x0B;
+const MODE_RESPONSE: u8 = 0x0C;
+const MODE_SEMAPHORE: u8 = 0x0D;
+const MODE_IR: u8 = 0x0E;
+
+// The RTC is a tiny microcontroller, always ready to take the next command
+const SEMAPHORE_READY: u8 = 0x01;
+
+// Commands sent in MODE_COMMAND: the command is in bits 4-6, its argument in bits 0-3
+const CMD_READ: u8 = 0x1;
+const CMD_WRITE: u8 = 0x2;
+const CMD_WRITE_NEXT: u8 = 0x3;
+const CMD_ADDRESS_LOW: u8 = 0x4;
+const CMD_ADDRESS_HIGH: u8 = 0x5;
+const CMD_EXTENDED: u8 = 0x6;
+const EXT_STATUS: u8 = 0x2;
+
+// The RTC's nibble-addressed memory
+const ADDR_MINUTES: u8 = 0x00; // 3 nibbles
+const ADDR_DAYS: u8 = 0x03; // 4 nibbles
+const ADDR_ALARM_MINUTES: u8 = 0x58; // 3 nibbles
+const ADDR_ALARM_DAYS: u8 = 0x5B; // 4 nibbles
+const ADDR_ALARM_ENABLED: u8 = 0x5F;
+
+const MINUTES_PER_DAY: u16 = 1440;
+const SECONDS_PER_MINUTE: u64 = 60;
+const MTIME_PER_MINUTE: u32 = MTIME_PER_SECOND * SECONDS_PER_MINUTE as u32;
+
+// Appended to the save in the same layout SameBoy uses: a u64 UNIX timestamp, then the
+// minutes, days, alarm minutes and alarm days as u16s, then the alarm enable flag, all little
+// endian
+const RTC_FOOTER_SIZE: usize = 17;
+
+/// The HuC3's clock, which only counts minutes and days.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Huc3Clock {
+    minutes: u16,
+    days: u16,
+    alarm_minutes: u16,
+    alarm_days: u16,
+    alarm_enabled: bool,
+}
+
+impl Huc3Clock {
+    fn read_nibble(&self, address: u8) -> u8 {
+        let nibble = |value: u16, base: u8| (value >> ((address - base) * 4)) as u8 & 0x0F;
+        match address {
+            0x00..=0x02 => nibble(self.minutes, ADDR_MINUTES),
+            0x03..=0x06 => nibble(self.days, ADDR_DAYS),
+            0x58..=0x5A => nibble(self.alarm_minutes, ADDR_ALARM_MINUTES),
+            0x5B..=0x5E => nibble(self.alarm_days, ADDR_ALARM_DAYS),
+            ADDR_ALARM_ENABLED => self.alarm_enabled as u8,
+            _ => 0,
+        }
+    }
+
+    fn write_nibble(&mut self, address: u8, value: u8) {
+        let set = |field: &mut u16, base: u8| {
+            let shift = (address - base) * 4;
+            *field = (*field & !(0x0F << shift)) | ((value as u16 & 0x0F) << shift);
+        };
+        match address {
+            0x00..=0x02 => set(&mut self.minutes, ADDR_MINUTES),
+            0x03..=0x06 => set(&mut self.days, ADDR_DAYS),
+            0x58..=0x5A => set(&mut self.alarm_minutes, ADDR_ALARM_MINUTES),
+            0x5B..=0x5E => set(&mut self.alarm_days, ADDR_ALARM_DAYS),
+            ADDR_ALARM_ENABLED => self.alarm_enabled = value & 0x01 != 0,
+            _ => debug!(
+                "Ignoring write to unknown HuC3 RTC address {}",
+                byte_fmt!(address)
+            ),
+        }
+    }
+
+    fn advance_minutes(&mut self, minutes: u64) {
+        let total = self.minutes as u64 + minutes;
+        self.minutes = (total % MINUTES_PER_DAY as u64) as u16;
+        // The day counter just wraps around
+        self.days = self
+            .days
+            .wrapping_add((total / MINUTES_PER_DAY as u64) as u16);
+    }
+
+    fn write_footer(&self, footer: &mut Vec<u8>, timestamp: u64) {
+        footer.extend_from_slice(&timestamp.to_le_bytes());
+        for value in [self.minutes, self.days, self.alarm_minutes, self.alarm_days] {
+            footer.extend_from_slice(&value.to_le_bytes());
+        }
+        footer.push(self.alarm_enabled as u8);
+    }
+
+    fn read_footer(footer: &[u8]) -> (Self, u64) {
+        let word = |i: usize| u16::from_le_bytes([footer[8 + i * 2], footer[9 + i * 2]]);
+        let clock = Self {
+            minutes: word(0),
+            days: word(1),
+            alarm_minutes: word(2),
+            alarm_days: word(3),
+            alarm_enabled: footer[16] & 0x01 != 0,
+        };
+        let timestamp = u64::from_le_bytes(footer[..8].try_into().unwrap());
+        (clock, timestamp)
+    }
+}
+
+#[derive(Debug)]
+pub struct CartHuc3 {
+    rom: Rom,
+    ram: Vec<u8>,
+    rom_banks: usize,
+    /// Set when RAM or the clock changes, so the save gets flushed.
+    save_dirty: bool,
+    ir: IrPort,
+
+    // Registers
+    mode: u8,
+    rom_bank: u8,
+    ram_bank: u8,
+
+    // RTC
+    clock: Huc3Clock,
+    rtc_cycles: u32,
+    rtc_address: u8,
+    rtc_response: u8,
+}
+
+impl CartHuc3 {
+    /// HuC3 carts always have a battery and a clock.
+    pub fn new(rom_banks: usize, ram_size: usize) -> Self {
+        Self {
+            rom: Rom::default(),
+            ram: vec![UNINIT_VALUE; ram_size],
+            rom_banks,
+            save_dirty: false,
+            ir: IrPort::default(),
+            mode: MODE_RAM_READ,
+            rom_bank: 1,
+            ram_bank: 0,
+            clock: Huc3Clock::default(),
+            rtc_cycles: 0,
+            rtc_address: 0,
+            rtc_response: 0,
+        }
+    }
+
+    fn ram_offset(&self, address: u16) -> Option<usize> {
+        if self.ram.is_empty() {
+            return None;
+        }
+        let offset =
+            self.ram_bank as usize * RAM_BANK_SIZE + CART_RAM.local_address(address) as usize;
+        Some(offset % self.ram.len())
+    }
+
+    fn run_command(&mut self, value: u8) {
+        let argument = value & 0x0F;
+        match (value >> 4) & 0x07 {
+            CMD_READ => {
+                self.rtc_response = self.clock.read_nibble(self.rtc_address);
+                self.rtc_address = self.rtc_address.wrapping_add(1);
+            }
+            CMD_WRITE => {
+                self.clock.write_nibble(self.rtc_address, argument);
+                self.save_dirty = true;
+            }
+            CMD_WRITE_NEXT => {
+                self.clock.write_nibble(self.rtc_address, argument);
+                self.rtc_address = self.rtc_address.wrapping_add(1);
+                self.save_dirty = true;
+            }
+            CMD_ADDRESS_LOW => self.rtc_address = (self.rtc_address & 0xF0) | argument,
+            CMD_ADDRESS_HIGH => self.rtc_address = (self.rtc_address & 0x0F) | (argument << 4),
+            CMD_EXTENDED if argument == EXT_STATUS => self.rtc_response = SEMAPHORE_READY,
+            _ => debug!("Ignoring unknown HuC3 RTC command {}", byte_fmt!(value)),
+        }
+    }
+}
+
+impl Cartridge for CartHuc3 {
+    fn init(&mut self) {
+        self.mode = MODE_RAM_READ;
+        self.rom_bank = 1;
+        self.ram_bank = 0;
+    }
+
+    fn step(&mut self, time: MTime) {
+        self.rtc_cycles += time.0 as u32;
+        while self.rtc_cycles >= MTIME_PER_MINUTE {
+            self.rtc_cycles -= MTIME_PER_MINUTE;
+            self.clock.advance_minutes(1);
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        region_guard!(address in ROM_SPACE);
+
+        if ROM_BANK_0.contains(address) {
+            self.rom.read(ROM_BANK_0.local_address(address) as usize)
+        } else {
+            self.rom
+                .read_bank(self.rom_bank as usize, ROM_BANK_N.local_address(address))
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        region_guard!(address in ROM_SPACE);
+
+        if MODE_SELECT.contains(address) {
+            self.mode = value & 0x0F;
+        } else if ROM_BANK.contains(address) {
+            self.rom_bank = value & ROM_BANK_MASK;
+        } else if RAM_BANK.contains(address) {
+            self.ram_bank = value & RAM_BANK_MASK;
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        region_guard!(address in CART_RAM);
+
+        match self.mode {
+            MODE_RAM_READ | MODE_RAM_WRITE => match self.ram_offset(address) {
+                Some(offset) => self.ram[offset],
+                None => OPEN_BUS_VALUE,
+            },
+            MODE_RESPONSE => self.rtc_response,
+            MODE_SEMAPHORE => SEMAPHORE_READY,
+            MODE_IR => self.ir.read(),
+            _ => OPEN_BUS_VALUE,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        region_guard!(address in CART_RAM);
+
+        match self.mode {
+            MODE_RAM_WRITE => {
+                if let Some(offset) = self.ram_offset(address) {
+                    self.ram[offset] = value;
+                    self.save_dirty = true;
+                }
+            }
+            MODE_COMMAND => self.run_command(value),
+            MODE_IR => self.ir.write(value),
+            // RAM is read-only in MODE_RAM_READ, and the semaphore ignores writes
+            _ => (),
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        let mut data = self.ram.to_vec();
+        // Backdate the timestamp to the start of the current minute, so the seconds already
+        // counted towards it aren't lost
+        let timestamp = unix_time().saturating_sub((self.rtc_cycles / MTIME_PER_SECOND) as u64);
+        self.clock.write_footer(&mut data, timestamp);
+        data
+    }
+
+    fn load_save_data(&mut self, data: &[u8], rtc_mode: RtcMode) {
+        let Some(footer) = load_ram_and_footer(&mut self.ram, data, &[RTC_FOOTER_SIZE]) else {
+            return;
+        };
+
+        let (clock, timestamp) = Huc3Clock::read_footer(footer);
+        self.clock = clock;
+
+        if rtc_mode == RtcMode::CatchUp {
+            let elapsed = unix_time().saturating_sub(timestamp);
+            info!("Advancing the RTC by {elapsed} seconds since the last save.");
+            self.clock.advance_minutes(elapsed / SECONDS_PER_MINUTE);
+            self.rtc_cycles = (elapsed % SECONDS_PER_MINUTE) as u32 * MTIME_PER_SECOND;
+        }
+    }
+
+    fn save_changed(&mut self) -> bool {
+        std::mem::take(&mut self.save_dirty)
+    }
+
+    fn load_from_bytes(&mut self, rom: &[u8]) -> Result<(), CartridgeError> {
+        self.rom = Rom::new(rom, self.rom_banks);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gb::hardware::cartridge::ROM_BANK_SIZE;
+    use test_log::test;
+
+    fn make_cart() -> CartHuc3 {
+        let mut cart = CartHuc3::new(4, 32 * 1024);
+        cart.load_from_bytes(&vec![0; 4 * ROM_BANK_SIZE]).unwrap();
+        cart.init();
+        cart
+    }
+
+    fn command(cart: &mut CartHuc3, command: u8, argument: u8) {
+        cart.write_rom(0x0000, MODE_COMMAND);
+        cart.write_ram(0xA000, (command << 4) | argument);
+    }
+
+    fn response(cart: &mut CartHuc3) -> u8 {
+        cart.write_rom(0x0000, MODE_RESPONSE);
+        cart.read_ram(0xA000)
+    }
+
+    fn set_address(cart: &mut CartHuc3, address: u8) {
+        command(cart, CMD_ADDRESS_LOW, address & 0x0F);
+        command(cart, CMD_ADDRESS_HIGH, address >> 4);
+    }
+
+    #[test]
+    fn test_ram_modes() {
+        let mut cart = make_cart();
+
+        // Read-only until mode $A
+        cart.write_ram(0xA000, 0x42);
+        assert_ne!(cart.read_ram(0xA000), 0x42);
+        cart.write_rom(0x0000, MODE_RAM_WRITE);
+        cart.write_ram(0xA000, 0x42);
+        cart.write_rom(0x0000, MODE_RAM_READ);
+        assert_eq!(cart.read_ram(0xA000), 0x42);
+
+        cart.write_rom(0x0000, MODE_SEMAPHORE);
+        assert_eq!(cart.read_ram(0xA000), SEMAPHORE_READY);
+        cart.write_rom(0x0000, MODE_IR);
+        assert_eq!(cart.read_ram(0xA000), IrPort::default().read());
+    }
+
+    #[test]
+    fn test_rtc_commands() {
+        let mut cart = make_cart();
+        cart.clock.minutes = 0x123;
+        cart.clock.days = 0x4567;
+
+        // Reads return one nibble at a time and move to the next address
+        set_address(&mut cart, ADDR_MINUTES);
+        let mut nibbles = Vec::new();
+        for _ in 0..7 {
+            command(&mut cart, CMD_READ, 0);
+            nibbles.push(response(&mut cart));
+        }
+        assert_eq!(nibbles, [0x3, 0x2, 0x1, 0x7, 0x6, 0x5, 0x4]);
+        assert!(!cart.save_changed());
+
+        // Writes do the same
+        set_address(&mut cart, ADDR_DAYS);
+        for nibble in [0x9, 0x8, 0x0, 0x0] {
+            command(&mut cart, CMD_WRITE_NEXT, nibble);
+        }
+        assert_eq!(cart.clock.days, 0x89);
+        assert!(cart.save_changed());
+
+        set_address(&mut cart, ADDR_ALARM_ENABLED);
+        command(&mut cart, CMD_WRITE, 1);
+        assert!(cart.clock.alarm_enabled);
+
+        command(&mut cart, CMD_EXTENDED, EXT_STATUS);
+        assert_eq!(response(&mut cart), SEMAPHORE_READY);
+    }
+
+    #[test]
+    fn test_clock() {
+        let mut cart = make_cart();
+        cart.clock.minutes = MINUTES_PER_DAY - 1;
+        cart.clock.days = 0xFFFF;
+
+        for _ in 0..(MTIME_PER_MINUTE / 0xFFFF) {
+            cart.step(MTime(0xFFFF));
+        }
+        assert_eq!(cart.clock.minutes, MINUTES_PER_DAY - 1);
+        cart.step(MTime(0xFFFF));
+        assert_eq!(cart.clock.minutes, 0);
+        assert_eq!(cart.clock.days, 0);
+    }
+
+    #[test]
+    fn test_footer() {
+        let mut cart = make_cart();
+        cart.clock = Huc3Clock {
+            minutes: 100,
+            days: 3,
+            alarm_minutes: 200,
+            alarm_days: 4,
+            alarm_enabled: true,
+        };
+        let data = cart.save_data();
+        assert_eq!(data.len(), 32 * 1024 + RTC_FOOTER_SIZE);
+
+        let mut frozen = make_cart();
+        frozen.load_save_data(&data, RtcMode::Frozen);
+        assert_eq!(frozen.clock, cart.clock);
+
+        // Catching up an hour and a half
+        let mut data = cart.ram.to_vec();
+        cart.clock.write_footer(&mut data, unix_time() - 5400);
+        let mut caught_up = make_cart();
+        caught_up.load_save_data(&data, RtcMode::CatchUp);
+        assert_eq!(caught_up.clock.minutes, 190);
+        assert_eq!(caught_up.clock.days, 3);
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{
+    gb::{
+        hardware::{
+            cartridge::{
+                Cartridge, CartridgeError, RAM_BANK_SIZE, ROM_BANK_0, ROM_BANK_N, RtcMode,
+                load_raw_ram, rom::Rom,
+            },
+            memory::{OPEN_BUS_VALUE, UNINIT_VALUE},
+        },
+        regions::{CART_RAM, MemoryRegion, ROM_SPACE},
+    },
+    region_guard,
+};
+
+const RAM_ENABLE: MemoryRegion = MemoryRegion::new(0x0000, 0x1FFF);
+const ROM_BANK: MemoryRegion = MemoryRegion::new(0x2000, 0x3FFF);
+const SECONDARY_BANK: MemoryRegion = MemoryRegion::new(0x4000, 0x5FFF);
+const BANKING_MODE: MemoryRegion = MemoryRegion::new(0x6000, 0x7FFF);
+
+const RAM_ENABLE_VALUE: u8 = 0x0A;
+const ROM_BANK_MASK: u8 = 0x1F;
+const SECONDARY_BANK_MASK: u8 = 0x03;
+
+/// How the secondary bank register is wired to the ROM's address lines.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Mbc1Wiring {
+    /// The secondary register drives ROM address bits 19-20 (bank bits 5-6).
+    #[default]
+    Normal,
+    /// MBC1M multicarts: the secondary register drives bank bits 4-5, and bit 4 of the ROM
+    /// bank register isn't connected.
+    Multicart,
+}
+
+impl Mbc1Wiring {
+    fn secondary_shift(self) -> usize {
+        match self {
+            Mbc1Wiring::Normal => 5,
+            Mbc1Wiring::Multicart => 4,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CartMbc1 {
+    rom: Rom,
+    ram: Vec<u8>,
+    rom_banks: usize,
+    has_battery: bool,
+    wiring: Mbc1Wiring,
+    ram_dirty: bool,
+
+    // Registers
+    ram_enabled: bool,
+    rom_bank: u8,
+    secondary_bank: u8,
+    advanced_banking: bool,
+}
+
+impl CartMbc1 {
+    pub fn new(rom_banks: usize, ram_size: usize, has_battery: bool, wiring: Mbc1Wiring) -> Self {
+        Self {
+            rom: Rom::default(),
+            ram: vec![UNINIT_VALUE; ram_size],
+            rom_banks,
+            has_battery,
+            wiring,
+            ram_dirty: false,
+            ram_enabled: false,
+            rom_bank: 1,
+            secondary_bank: 0,
+            advanced_banking: false,
+        }
+    }
+
+    fn upper_bank_bits(&self) -> usize {
+        (self.secondary_bank as usize) << self.wiring.secondary_shift()
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        let bank = if self.advanced_banking {
+            self.secondary_bank as usize
+        } else {
+            0
+        };
+        // Carts with less than a full bank of RAM (or fewer banks) mirror it
+        (bank * RAM_BANK_SIZE + CART_RAM.local_address(address) as usize) % self.ram.len()
+    }
+}
+
+impl Cartridge for CartMbc1 {
+    fn init(&mut self) {
+        self.ram_enabled = false;
+        self.rom_bank = 1;
+        self.secondary_bank = 0;
+        self.advanced_banking = false;
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        region_guard!(address in ROM_SPACE);
+
+        // Bank numbers wrap around to however many banks the cart actually has
+        if ROM_BANK_0.contains(address) {
+            // In advanced banking mode, the secondary register also affects the "fixed" bank
+            let bank = if self.advanced_banking {
+                self.upper_bank_bits()
+            } else {
+                0
+            };
+            self.rom.read_bank(bank, ROM_BANK_0.local_address(address))
+        } else {
+            let lower_mask = (1 << self.wiring.secondary_shift()) - 1;
+            let bank = self.upper_bank_bits() | (self.rom_bank as usize & lower_mask);
+            self.rom.read_bank(bank, ROM_BANK_N.local_address(address))
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        region_guard!(address in ROM_SPACE);
+
+        if RAM_ENABLE.contains(address) {
+            self.ram_enabled = (value & 0x0F) == RAM_ENABLE_VALUE;
+        } else if ROM_BANK.contains(address) {
+            // Bank 0 can't be selected here; 0 is treated as 1. This check only looks at the
+            // 5 bits of the register, so on smaller carts a masked-off value can still map bank 0.
+            self.rom_bank = match value & ROM_BANK_MASK {
+                0 => 1,
+                bank => bank,
+            };
+        } else if SECONDARY_BANK.contains(address) {
+            self.secondary_bank = value & SECONDARY_BANK_MASK;
+        } else if BANKING_MODE.contains(address) {
+            self.advanced_banking = (value & 0x01) != 0;
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        region_guard!(address in CART_RAM);
+
+        if self.ram_enabled && !self.ram.is_empty() {
+            self.ram[self.ram_offset(address)]
+        } else {
+            OPEN_BUS_VALUE
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        region_guard!(address in CART_RAM);
+
+        if self.ram_enabled && !self.ram.is_empty() {
+            let offset = self.ram_offset(address);
+            self.ram[offset] = value;
+            self.ram_dirty = true;
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn load_save_data(&mut self, data: &[u8], rtc_mode: RtcMode) {
+        load_raw_ram(&mut self.ram, data);
+    }
+
+    fn save_changed(&mut self) -> bool {
+        std::mem::take(&mut self.ram_dirty)
+    }
+
+    fn load_from_bytes(&mut self, rom: &[u8]) -> Result<(), CartridgeError> {
+        self.rom = Rom::new(rom, self.rom_banks);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gb::hardware::cartridge::{ROM_BANK_SIZE, bank_numbered_rom};
+    use test_log::test;
+
+    /// Makes a cart where the first byte of every ROM bank is that bank's number.
+    fn make_cart(rom_banks: usize, ram_size: usize) -> CartMbc1 {
+        make_cart_wired(rom_banks, ram_size, Mbc1Wiring::Normal)
+    }
+
+    fn make_cart_wired(rom_banks: usize, ram_size: usize, wiring: Mbc1Wiring) -> CartMbc1 {
+        let mut cart = CartMbc1::new(rom_banks, ram_size, false, wiring);
+        cart.load_from_bytes(&bank_numbered_rom(ROM_BANK_SIZE, rom_banks))
+            .unwrap();
+        cart.init();
+        cart
+    }
+
+    #[test]
+    fn test_rom_banking() {
+        let mut cart = make_cart(128, 0);
+        assert_eq!(cart.read_rom(0x0000), 0);
+        assert_eq!(cart.read_rom(0x4000), 1);
+
+        cart.write_rom(0x2000, 0x05);
+        assert_eq!(cart.read_rom(0x4000), 5);
+
+        // Secondary register supplies bits 5-6
+        cart.write_rom(0x4000, 0x02);
+        assert_eq!(cart.read_rom(0x4000), 0x45);
+        assert_eq!(cart.read_rom(0x0000), 0);
+
+        // Advanced banking mode remaps the bank 0 area too
+        cart.write_rom(0x6000, 0x01);
+        assert_eq!(cart.read_rom(0x0000), 0x40);
+    }
+
+    #[test]
+    fn test_bank_0_quirk() {
+        let mut cart = make_cart(128, 0);
+        for (value, expected) in [(0x00, 0x01), (0x20, 0x21), (0x40, 0x41), (0x60, 0x61)] {
+            cart.write_rom(0x4000, value >> 5);
+            cart.write_rom(0x2000, 0x00);
+            assert_eq!(cart.read_rom(0x4000), expected);
+        }
+
+        // On a 256 KiB cart, the check happens before masking, so bank $10 maps to bank 0
+        let mut cart = make_cart(16, 0);
+        cart.write_rom(0x2000, 0x10);
+        assert_eq!(cart.read_rom(0x4000), 0);
+    }
+
+    #[test]
+    fn test_multicart_wiring() {
+        let mut cart = make_cart_wired(64, 0, Mbc1Wiring::Multicart);
+
+        // Bit 4 of the ROM bank register is ignored...
+        cart.write_rom(0x2000, 0x13);
+        assert_eq!(cart.read_rom(0x4000), 0x03);
+
+        // ...and the secondary register selects which 256 KiB game is mapped
+        cart.write_rom(0x4000, 0x02);
+        assert_eq!(cart.read_rom(0x4000), 0x23);
+        cart.write_rom(0x6000, 0x01);
+        assert_eq!(cart.read_rom(0x0000), 0x20);
+
+        // Writing 0 still selects bank 1 of the game
+        cart.write_rom(0x2000, 0x00);
+        assert_eq!(cart.read_rom(0x4000), 0x21);
+    }
+
+    #[test]
+    fn test_ram_gating_and_banking() {
+        let mut cart = make_cart(4, 32 * 1024);
+
+        // Disabled by default
+        cart.write_ram(0xA000, 0x12);
+        assert_eq!(cart.read_ram(0xA000), OPEN_BUS_VALUE);
+
+        cart.write_rom(0x0000, 0x0A);
+        cart.write_ram(0xA000, 0x12);
+        assert_eq!(cart.read_ram(0xA000), 0x12);
+
+        // RAM banks are only switched in advanced banking mode
+        cart.write_rom(0x4000, 0x01);
+        assert_eq!(cart.read_ram(0xA000), 0x12);
+        cart.write_rom(0x6000, 0x01);
+        assert_eq!(cart.read_ram(0xA000), UNINIT_VALUE);
+        cart.write_ram(0xA000, 0x34);
+        cart.write_rom(0x4000, 0x00);
+        assert_eq!(cart.read_ram(0xA000), 0x12);
+
+        // Any value without $A in the low nibble disables it again
+        cart.write_rom(0x0000, 0x00);
+        assert_eq!(cart.read_ram(0xA000), OPEN_BUS_VALUE);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::{
+    gb::{
+        hardware::{
+            cartridge::{
+                Cartridge, CartridgeError, ROM_BANK_0, ROM_BANK_N, RtcMode, load_raw_ram, rom::Rom,
+            },
+            memory::{OPEN_BUS_VALUE, UNINIT_VALUE},
+        },
+        regions::{CART_RAM, MemoryRegion, ROM_SPACE},
+    },
+    region_guard,
+};
+
+const REGISTERS: MemoryRegion = MemoryRegion::new(0x0000, 0x3FFF);
+const REGISTER_SELECT_BIT: u16 = 0x0100;
+
+const RAM_ENABLE_VALUE: u8 = 0x0A;
+const ROM_BANK_MASK: u8 = 0x0F;
+
+// 512 half-bytes of RAM built into the MBC itself
+const RAM_SIZE: usize = 512;
+const RAM_ADDRESS_MASK: u16 = 0x01FF;
+const RAM_VALUE_MASK: u8 = 0x0F;
+
+#[derive(Debug)]
+pub struct CartMbc2 {
+    rom: Rom,
+    ram: [u8; RAM_SIZE],
+    rom_banks: usize,
+    has_battery: bool,
+    ram_dirty: bool,
+
+    // Registers
+    ram_enabled: bool,
+    rom_bank: u8,
+}
+
+impl CartMbc2 {
+    pub fn new(rom_banks: usize, has_battery: bool) -> Self {
+        Self {
+            rom: Rom::default(),
+            ram: [UNINIT_VALUE & RAM_VALUE_MASK; RAM_SIZE],
+            rom_banks,
+            has_battery,
+            ram_dirty: false,
+            ram_enabled: false,
+            rom_bank: 1,
+        }
+    }
+
+    fn ram_offset(address: u16) -> usize {
+        // Only the bottom 9 bits are used, so the RAM echoes across all of CART_RAM
+        (CART_RAM.local_address(address) & RAM_ADDRESS_MASK) as usize
+    }
+}
+
+impl Cartridge for CartMbc2 {
+    fn init(&mut self) {
+        self.ram_enabled = false;
+        self.rom_bank = 1;
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        region_guard!(address in ROM_SPACE);
+
+        if ROM_BANK_0.contains(address) {
+            self.rom.read(ROM_BANK_0.local_address(address) as usize)
+        } else {
+            // Bank numbers past the end of the ROM mirror back into it
+            self.rom
+                .read_bank(self.rom_bank as usize, ROM_BANK_N.local_address(address))
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        region_guard!(address in ROM_SPACE);
+
+        if REGISTERS.contains(address) {
+            // Bit 8 of the address decides which register is written
+            if address & REGISTER_SELECT_BIT == 0 {
+                self.ram_enabled = (value & 0x0F) == RAM_ENABLE_VALUE;
+            } else {
+                self.rom_bank = match value & ROM_BANK_MASK {
+                    0 => 1,
+                    bank => bank,
+                };
+            }
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        region_guard!(address in CART_RAM);
+
+        if self.ram_enabled {
+            // Only the lower 4 bits exist; the upper ones read as 1
+            self.ram[Self::ram_offset(address)] | !RAM_VALUE_MASK
+        } else {
+            OPEN_BUS_VALUE
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        region_guard!(address in CART_RAM);
+
+        if self.ram_enabled {
+            self.ram[Self::ram_offset(address)] = value & RAM_VALUE_MASK;
+            self.ram_dirty = true;
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn load_save_data(&mut self, data: &[u8], rtc_mode: RtcMode) {
+        load_raw_ram(&mut self.ram, data);
+        // Some emulators save the upper (nonexistent) bits as 1s
+        for value in self.ram.iter_mut() {
+            *value &= RAM_VALUE_MASK;
+        }
+    }
+
+    fn save_changed(&mut self) -> bool {
+        std::mem::take(&mut self.ram_dirty)
+    }
+
+    fn load_from_bytes(&mut self, rom: &[u8]) -> Result<(), CartridgeError> {
+        self.rom = Rom::new(rom, self.rom_banks);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gb::hardware::cartridge::{ROM_BANK_SIZE, bank_numbered_rom};
+    use test_log::test;
+
+    /// Makes a cart where the first byte of every ROM bank is that bank's number.
+    fn make_cart(rom_banks: usize) -> CartMbc2 {
+        let mut cart = CartMbc2::new(rom_banks, false);
+        cart.load_from_bytes(&bank_numbered_rom(ROM_BANK_SIZE, rom_banks))
+            .unwrap();
+        cart.init();
+        cart
+    }
+
+    #[test]
+    fn test_register_select() {
+        let mut cart = make_cart(16);
+
+        // Bit 8 set: ROM bank
+        cart.write_rom(0x2100, 0x0C);
+        assert_eq!(cart.read_rom(0x4000), 0x0C);
+        cart.write_rom(0x0100, 0x00);
+        assert_eq!(cart.read_rom(0x4000), 0x01);
+
+        // Bit 8 clear: RAM enable, and the ROM bank doesn't change
+        cart.write_rom(0x3E00, 0x0A);
+        assert!(cart.ram_enabled);
+        assert_eq!(cart.read_rom(0x4000), 0x01);
+    }
+
+    #[test]
+    fn test_half_byte_ram() {
+        let mut cart = make_cart(2);
+        cart.write_rom(0x0000, 0x0A);
+
+        cart.write_ram(0xA000, 0x5C);
+        assert_eq!(cart.read_ram(0xA000), 0xFC);
+
+        // Echoes every 512 bytes
+        assert_eq!(cart.read_ram(0xA200), 0xFC);
+        assert_eq!(cart.read_ram(0xBE00), 0xFC);
+        cart.write_ram(0xBFFF, 0x03);
+        assert_eq!(cart.read_ram(0xA1FF), 0xF3);
+
+        cart.write_rom(0x0000, 0x00);
+        assert_eq!(cart.read_ram(0xA000), OPEN_BUS_VALUE);
+    }
+}
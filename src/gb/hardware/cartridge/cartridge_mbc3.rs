@@ -0,0 +1,601 @@
+use crate::{
+    gb::{
+        MTime,
+        hardware::{
+            cartridge::{
+                Cartridge, CartridgeError, MTIME_PER_SECOND, RAM_BANK_SIZE, ROM_BANK_0, ROM_BANK_N,
+                RtcMode, load_ram_and_footer, load_raw_ram, rom::Rom, unix_time,
+            },
+            memory::{OPEN_BUS_VALUE, UNINIT_VALUE},
+        },
+        regions::{CART_RAM, MemoryRegion, ROM_SPACE},
+    },
+    region_guard,
+};
+use log::info;
+
+const RAM_ENABLE: MemoryRegion = MemoryRegion::new(0x0000, 0x1FFF);
+const ROM_BANK: MemoryRegion = MemoryRegion::new(0x2000, 0x3FFF);
+const RAM_BANK: MemoryRegion = MemoryRegion::new(0x4000, 0x5FFF);
+const LATCH_CLOCK: MemoryRegion = MemoryRegion::new(0x6000, 0x7FFF);
+
+const RAM_ENABLE_VALUE: u8 = 0x0A;
+const ROM_BANK_MASK: u8 = 0x7F;
+const RAM_BANK_MASK: u8 = 0x03;
+
+// MBC30 (only used by Pocket Monsters: Crystal Version) has one more bit in each bank register
+const MBC30_ROM_BANK_MASK: u8 = 0xFF;
+const MBC30_RAM_BANK_MASK: u8 = 0x07;
+const MBC3_MAX_ROM_BANKS: usize = 128;
+const MBC3_MAX_RAM_SIZE: usize = 32 * 1024;
+
+// RTC register select values (written to RAM_BANK)
+const RTC_S: u8 = 0x08;
+const RTC_M: u8 = 0x09;
+const RTC_H: u8 = 0x0A;
+const RTC_DL: u8 = 0x0B;
+const RTC_DH: u8 = 0x0C;
+
+// Bits in RTC_DH
+const DH_DAY_HIGH: u8 = 0x01;
+const DH_HALT: u8 = 0x40;
+const DH_DAY_CARRY: u8 = 0x80;
+
+// BGB and VBA-M append the clock to the save file: the live registers and then the latched ones
+// as little-endian u32s, followed by a UNIX timestamp (a u64 in BGB, a u32 in older VBA-M)
+const RTC_FOOTER_REGS: [u8; 5] = [RTC_S, RTC_M, RTC_H, RTC_DL, RTC_DH];
+const RTC_FOOTER_SIZE: usize = 48;
+const RTC_FOOTER_SIZE_32: usize = 44;
+const RTC_FOOTER_TIMESTAMP: usize = 40;
+
+/// The raw values of the five RTC registers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct RtcRegisters {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    day_low: u8,
+    day_high: u8,
+}
+
+impl RtcRegisters {
+    fn get(&self, select: u8) -> u8 {
+        match select {
+            RTC_S => self.seconds,
+            RTC_M => self.minutes,
+            RTC_H => self.hours,
+            RTC_DL => self.day_low,
+            RTC_DH => self.day_high,
+            _ => OPEN_BUS_VALUE,
+        }
+    }
+
+    fn set(&mut self, select: u8, value: u8) {
+        // Unused bits aren't stored and read back as 0
+        match select {
+            RTC_S => self.seconds = value & 0x3F,
+            RTC_M => self.minutes = value & 0x3F,
+            RTC_H => self.hours = value & 0x1F,
+            RTC_DL => self.day_low = value,
+            RTC_DH => self.day_high = value & (DH_DAY_HIGH | DH_HALT | DH_DAY_CARRY),
+            _ => (),
+        }
+    }
+
+    fn halted(&self) -> bool {
+        self.day_high & DH_HALT != 0
+    }
+
+    fn days(&self) -> u16 {
+        (((self.day_high & DH_DAY_HIGH) as u16) << 8) | self.day_low as u16
+    }
+
+    fn set_days(&mut self, days: u16) {
+        self.day_low = days as u8;
+        self.day_high = (self.day_high & !DH_DAY_HIGH) | ((days >> 8) as u8 & DH_DAY_HIGH);
+        if days > 0x1FF {
+            self.day_high |= DH_DAY_CARRY;
+        }
+    }
+
+    fn write_footer(&self, footer: &mut Vec<u8>) {
+        for select in RTC_FOOTER_REGS {
+            footer.extend_from_slice(&(self.get(select) as u32).to_le_bytes());
+        }
+    }
+
+    fn read_footer(footer: &[u8]) -> Self {
+        let mut regs = Self::default();
+        for (i, select) in RTC_FOOTER_REGS.into_iter().enumerate() {
+            regs.set(select, footer[i * 4]);
+        }
+        regs
+    }
+
+    /// Advances the clock by many seconds at once, exactly as if tick_second was called that
+    /// many times.
+    fn advance_seconds(&mut self, mut seconds: u64) {
+        // Out of range counters don't carry normally, so step through those one at a time
+        while seconds > 0 && (self.seconds >= 60 || self.minutes >= 60 || self.hours >= 24) {
+            self.tick_second();
+            seconds -= 1;
+        }
+        if seconds == 0 {
+            return;
+        }
+
+        let total = self.days() as u64 * 86400
+            + self.hours as u64 * 3600
+            + self.minutes as u64 * 60
+            + self.seconds as u64
+            + seconds;
+        self.seconds = (total % 60) as u8;
+        self.minutes = (total / 60 % 60) as u8;
+        self.hours = (total / 3600 % 24) as u8;
+
+        // The day counter overflows into the carry bit, and keeps counting from 0
+        let days = total / 86400;
+        self.set_days((days & 0x1FF) as u16);
+        if days > 0x1FF {
+            self.day_high |= DH_DAY_CARRY;
+        }
+    }
+
+    /// Advances the clock by one second. Each counter only carries into the next one when it
+    /// reaches its natural limit; one that was set out of range instead wraps at its bit width
+    /// without carrying.
+    fn tick_second(&mut self) {
+        if !Self::tick_counter(&mut self.seconds, 60, 0x3F) {
+            return;
+        }
+        if !Self::tick_counter(&mut self.minutes, 60, 0x3F) {
+            return;
+        }
+        if !Self::tick_counter(&mut self.hours, 24, 0x1F) {
+            return;
+        }
+
+        self.set_days(self.days() + 1);
+    }
+
+    fn tick_counter(counter: &mut u8, limit: u8, mask: u8) -> bool {
+        *counter = (*counter + 1) & mask;
+        if *counter == limit {
+            *counter = 0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CartMbc3 {
+    rom: Rom,
+    ram: Vec<u8>,
+    rom_banks: usize,
+    has_battery: bool,
+    has_rtc: bool,
+    rom_bank_mask: u8,
+    ram_bank_mask: u8,
+    /// Set when RAM or the clock changes, so the save gets flushed.
+    save_dirty: bool,
+
+    // Registers
+    ram_enabled: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+    latch_armed: bool,
+
+    // RTC
+    rtc: RtcRegisters,
+    rtc_latched: RtcRegisters,
+    rtc_cycles: u32,
+}
+
+impl CartMbc3 {
+    pub fn new(rom_banks: usize, ram_size: usize, has_battery: bool, has_rtc: bool) -> Self {
+        // MBC30 carts use the same header values as MBC3, so they can only be told apart by
+        // needing more ROM or RAM than a plain MBC3 can address
+        let is_mbc30 = rom_banks > MBC3_MAX_ROM_BANKS || ram_size > MBC3_MAX_RAM_SIZE;
+        let (rom_bank_mask, ram_bank_mask) = if is_mbc30 {
+            info!(
+                "MBC3 cart has {rom_banks} ROM banks and {ram_size} bytes of RAM; treating it as MBC30."
+            );
+            (MBC30_ROM_BANK_MASK, MBC30_RAM_BANK_MASK)
+        } else {
+            (ROM_BANK_MASK, RAM_BANK_MASK)
+        };
+
+        Self {
+            rom: Rom::default(),
+            ram: vec![UNINIT_VALUE; ram_size],
+            rom_banks,
+            has_battery,
+            has_rtc,
+            rom_bank_mask,
+            ram_bank_mask,
+            save_dirty: false,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            latch_armed: false,
+            rtc: RtcRegisters::default(),
+            rtc_latched: RtcRegisters::default(),
+            rtc_cycles: 0,
+        }
+    }
+
+    fn rtc_selected(&self) -> bool {
+        self.has_rtc && (RTC_S..=RTC_DH).contains(&self.ram_bank)
+    }
+
+    fn ram_offset(&self, address: u16) -> Option<usize> {
+        if self.ram.is_empty() || self.ram_bank > self.ram_bank_mask {
+            return None;
+        }
+        let offset =
+            self.ram_bank as usize * RAM_BANK_SIZE + CART_RAM.local_address(address) as usize;
+        Some(offset % self.ram.len())
+    }
+}
+
+impl Cartridge for CartMbc3 {
+    fn init(&mut self) {
+        self.ram_enabled = false;
+        self.rom_bank = 1;
+        self.ram_bank = 0;
+        self.latch_armed = false;
+    }
+
+    fn step(&mut self, time: MTime) {
+        if !self.has_rtc || self.rtc.halted() {
+            return;
+        }
+
+        self.rtc_cycles += time.0 as u32;
+        while self.rtc_cycles >= MTIME_PER_SECOND {
+            self.rtc_cycles -= MTIME_PER_SECOND;
+            self.rtc.tick_second();
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        region_guard!(address in ROM_SPACE);
+
+        if ROM_BANK_0.contains(address) {
+            self.rom.read(ROM_BANK_0.local_address(address) as usize)
+        } else {
+            // Bank numbers past the end of the ROM mirror back into it
+            self.rom
+                .read_bank(self.rom_bank as usize, ROM_BANK_N.local_address(address))
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        region_guard!(address in ROM_SPACE);
+
+        if RAM_ENABLE.contains(address) {
+            self.ram_enabled = (value & 0x0F) == RAM_ENABLE_VALUE;
+        } else if ROM_BANK.contains(address) {
+            self.rom_bank = match value & self.rom_bank_mask {
+                0 => 1,
+                bank => bank,
+            };
+        } else if RAM_BANK.contains(address) {
+            self.ram_bank = value;
+        } else if LATCH_CLOCK.contains(address) {
+            // Writing $00 then $01 copies the live clock into the readable registers
+            if self.latch_armed && value == 0x01 {
+                self.rtc_latched = self.rtc;
+                self.save_dirty |= self.has_rtc;
+            }
+            self.latch_armed = value == 0x00;
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        region_guard!(address in CART_RAM);
+
+        if !self.ram_enabled {
+            OPEN_BUS_VALUE
+        } else if self.rtc_selected() {
+            self.rtc_latched.get(self.ram_bank)
+        } else {
+            match self.ram_offset(address) {
+                Some(offset) => self.ram[offset],
+                None => OPEN_BUS_VALUE,
+            }
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        region_guard!(address in CART_RAM);
+
+        if !self.ram_enabled {
+            // Ignore writes
+        } else if self.rtc_selected() {
+            if self.ram_bank == RTC_S {
+                // Writing the seconds resets the sub-second divider
+                self.rtc_cycles = 0;
+            }
+            self.rtc.set(self.ram_bank, value);
+            self.rtc_latched.set(self.ram_bank, value);
+            self.save_dirty = true;
+        } else if let Some(offset) = self.ram_offset(address) {
+            self.ram[offset] = value;
+            self.save_dirty = true;
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        let mut data = self.ram.to_vec();
+        if self.has_rtc {
+            self.rtc.write_footer(&mut data);
+            self.rtc_latched.write_footer(&mut data);
+            data.extend_from_slice(&unix_time().to_le_bytes());
+        }
+        data
+    }
+
+    fn load_save_data(&mut self, data: &[u8], rtc_mode: RtcMode) {
+        if !self.has_rtc {
+            load_raw_ram(&mut self.ram, data);
+            return;
+        }
+        let footer_sizes = [RTC_FOOTER_SIZE, RTC_FOOTER_SIZE_32];
+        let Some(footer) = load_ram_and_footer(&mut self.ram, data, &footer_sizes) else {
+            return;
+        };
+
+        let timestamp = if footer.len() == RTC_FOOTER_SIZE {
+            u64::from_le_bytes(footer[RTC_FOOTER_TIMESTAMP..].try_into().unwrap())
+        } else {
+            u32::from_le_bytes(footer[RTC_FOOTER_TIMESTAMP..].try_into().unwrap()) as u64
+        };
+
+        self.rtc = RtcRegisters::read_footer(footer);
+        self.rtc_latched = RtcRegisters::read_footer(&footer[RTC_FOOTER_REGS.len() * 4..]);
+
+        if rtc_mode == RtcMode::CatchUp && !self.rtc.halted() {
+            let elapsed = unix_time().saturating_sub(timestamp);
+            info!("Advancing the RTC by {elapsed} seconds since the last save.");
+            self.rtc.advance_seconds(elapsed);
+        }
+    }
+
+    fn save_changed(&mut self) -> bool {
+        std::mem::take(&mut self.save_dirty)
+    }
+
+    fn load_from_bytes(&mut self, rom: &[u8]) -> Result<(), CartridgeError> {
+        self.rom = Rom::new(rom, self.rom_banks);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gb::hardware::cartridge::{ROM_BANK_SIZE, bank_numbered_rom};
+    use test_log::test;
+
+    /// Makes a cart where the first byte of every ROM bank is that bank's number.
+    fn make_cart(rom_banks: usize, ram_size: usize) -> CartMbc3 {
+        let mut cart = CartMbc3::new(rom_banks, ram_size, true, true);
+        cart.load_from_bytes(&bank_numbered_rom(ROM_BANK_SIZE, rom_banks))
+            .unwrap();
+        cart.init();
+        cart.write_rom(0x0000, 0x0A);
+        cart
+    }
+
+    fn latch(cart: &mut CartMbc3) {
+        cart.write_rom(0x6000, 0x00);
+        cart.write_rom(0x6000, 0x01);
+    }
+
+    fn read_rtc(cart: &mut CartMbc3, select: u8) -> u8 {
+        cart.write_rom(0x4000, select);
+        cart.read_ram(0xA000)
+    }
+
+    fn write_rtc(cart: &mut CartMbc3, select: u8, value: u8) {
+        cart.write_rom(0x4000, select);
+        cart.write_ram(0xA000, value);
+    }
+
+    #[test]
+    fn test_banking() {
+        let mut cart = make_cart(128, 32 * 1024);
+
+        cart.write_rom(0x2000, 0x7F);
+        assert_eq!(cart.read_rom(0x4000), 0x7F);
+        cart.write_rom(0x2000, 0x00);
+        assert_eq!(cart.read_rom(0x4000), 0x01);
+
+        for bank in 0..4 {
+            cart.write_rom(0x4000, bank);
+            cart.write_ram(0xA000, bank + 0x10);
+        }
+        for bank in 0..4 {
+            cart.write_rom(0x4000, bank);
+            assert_eq!(cart.read_ram(0xA000), bank + 0x10);
+        }
+    }
+
+    #[test]
+    fn test_mbc30_banking() {
+        // A plain MBC3 only has 7 ROM bank bits and 2 RAM bank bits
+        let mut cart = make_cart(128, 32 * 1024);
+        assert_eq!(cart.rom_bank_mask, ROM_BANK_MASK);
+        cart.write_rom(0x4000, 0x04);
+        assert_eq!(cart.read_ram(0xA000), OPEN_BUS_VALUE);
+
+        let mut cart = make_cart(256, 64 * 1024);
+        cart.write_rom(0x2000, 0xFF);
+        assert_eq!(cart.read_rom(0x4000), 0xFF);
+
+        for bank in 0..8 {
+            cart.write_rom(0x4000, bank);
+            cart.write_ram(0xA000, bank + 0x10);
+        }
+        for bank in 0..8 {
+            cart.write_rom(0x4000, bank);
+            assert_eq!(cart.read_ram(0xA000), bank + 0x10);
+        }
+
+        // Either one is enough to need MBC30 banking
+        assert_eq!(
+            CartMbc3::new(256, 0, false, false).rom_bank_mask,
+            MBC30_ROM_BANK_MASK
+        );
+        assert_eq!(
+            CartMbc3::new(2, 64 * 1024, false, false).ram_bank_mask,
+            MBC30_RAM_BANK_MASK
+        );
+    }
+
+    #[test]
+    fn test_rtc_latch() {
+        let mut cart = make_cart(2, 0);
+
+        cart.step(MTime(0xFFFF));
+        for _ in 0..(MTIME_PER_SECOND / 0xFFFF) {
+            cart.step(MTime(0xFFFF));
+        }
+
+        // Not latched yet
+        assert_eq!(read_rtc(&mut cart, RTC_S), 0);
+        assert!(!cart.save_changed());
+        latch(&mut cart);
+        assert_eq!(read_rtc(&mut cart, RTC_S), 1);
+        // Latching changes what gets saved, so it needs flushing like a RAM write
+        assert!(cart.save_changed());
+
+        // Latched values don't change as time passes
+        cart.step(MTime(0xFFFF));
+        for _ in 0..(MTIME_PER_SECOND / 0xFFFF) {
+            cart.step(MTime(0xFFFF));
+        }
+        assert_eq!(read_rtc(&mut cart, RTC_S), 1);
+
+        // Writing $01 without $00 first doesn't latch
+        cart.write_rom(0x6000, 0x01);
+        assert_eq!(read_rtc(&mut cart, RTC_S), 1);
+        latch(&mut cart);
+        assert_eq!(read_rtc(&mut cart, RTC_S), 2);
+
+        // So does setting the clock
+        assert!(cart.save_changed());
+        cart.write_ram(0xA000, 30);
+        assert!(cart.save_changed());
+    }
+
+    #[test]
+    fn test_rtc_carry_and_halt() {
+        let mut cart = make_cart(2, 0);
+        write_rtc(&mut cart, RTC_S, 59);
+        write_rtc(&mut cart, RTC_M, 59);
+        write_rtc(&mut cart, RTC_H, 23);
+        write_rtc(&mut cart, RTC_DL, 0xFF);
+        write_rtc(&mut cart, RTC_DH, DH_DAY_HIGH);
+
+        cart.rtc.tick_second();
+        latch(&mut cart);
+        assert_eq!(read_rtc(&mut cart, RTC_S), 0);
+        assert_eq!(read_rtc(&mut cart, RTC_M), 0);
+        assert_eq!(read_rtc(&mut cart, RTC_H), 0);
+        assert_eq!(read_rtc(&mut cart, RTC_DL), 0);
+        assert_eq!(read_rtc(&mut cart, RTC_DH), DH_DAY_CARRY);
+
+        // Out of range values wrap without carrying
+        write_rtc(&mut cart, RTC_S, 63);
+        cart.rtc.tick_second();
+        assert_eq!(cart.rtc.seconds, 0);
+        assert_eq!(cart.rtc.minutes, 0);
+
+        // No time passes while halted
+        write_rtc(&mut cart, RTC_DH, DH_HALT);
+        cart.step(MTime(0xFFFF));
+        for _ in 0..(MTIME_PER_SECOND / 0xFFFF) {
+            cart.step(MTime(0xFFFF));
+        }
+        assert_eq!(cart.rtc.seconds, 0);
+    }
+
+    #[test]
+    fn test_advance_seconds() {
+        // Bulk advancing should match ticking one second at a time, including across carries
+        let start = RtcRegisters {
+            seconds: 58,
+            minutes: 59,
+            hours: 23,
+            day_low: 0xFE,
+            day_high: DH_DAY_HIGH,
+        };
+        for seconds in [1, 2, 3, 86_400, 86_400 * 3 + 61] {
+            let mut ticked = start;
+            for _ in 0..seconds {
+                ticked.tick_second();
+            }
+            let mut advanced = start;
+            advanced.advance_seconds(seconds);
+            assert_eq!(advanced, ticked, "after {seconds} seconds");
+        }
+
+        // Out of range values
+        let start = RtcRegisters {
+            seconds: 62,
+            minutes: 61,
+            ..RtcRegisters::default()
+        };
+        let mut ticked = start;
+        for _ in 0..4000 {
+            ticked.tick_second();
+        }
+        let mut advanced = start;
+        advanced.advance_seconds(4000);
+        assert_eq!(advanced, ticked);
+    }
+
+    #[test]
+    fn test_rtc_footer() {
+        let mut cart = make_cart(2, 8 * 1024);
+        cart.write_ram(0xA000, 0x99);
+        write_rtc(&mut cart, RTC_M, 12);
+        latch(&mut cart);
+        write_rtc(&mut cart, RTC_H, 5);
+
+        let data = cart.save_data();
+        assert_eq!(data.len(), 8 * 1024 + RTC_FOOTER_SIZE);
+        assert_eq!(data[8 * 1024 + 4], 12);
+        assert_eq!(data[8 * 1024 + 8], 5);
+
+        // Freezing the clock restores it exactly
+        let mut frozen = make_cart(2, 8 * 1024);
+        frozen.load_save_data(&data, RtcMode::Frozen);
+        assert_eq!(frozen.read_ram(0xA000), 0x99);
+        assert_eq!(frozen.rtc, cart.rtc);
+        assert_eq!(frozen.rtc_latched, cart.rtc_latched);
+
+        // Catching up counts the time since the timestamp
+        let mut old_save = data.clone();
+        let an_hour_ago = unix_time() - 3600;
+        old_save[8 * 1024 + RTC_FOOTER_TIMESTAMP..].copy_from_slice(&an_hour_ago.to_le_bytes());
+        let mut caught_up = make_cart(2, 8 * 1024);
+        caught_up.load_save_data(&old_save, RtcMode::CatchUp);
+        assert_eq!(caught_up.rtc.hours, 6);
+        assert_eq!(caught_up.rtc.minutes, 12);
+
+        // VBA-M style 32-bit timestamps work too
+        let short_save = &data[..data.len() - 4];
+        let mut short = make_cart(2, 8 * 1024);
+        short.load_save_data(short_save, RtcMode::Frozen);
+        assert_eq!(short.rtc, cart.rtc);
+    }
+}
@@ -0,0 +1,244 @@
+use crate::{
+    gb::{
+        hardware::{
+            cartridge::{
+                Cartridge, CartridgeError, RAM_BANK_SIZE, ROM_BANK_0, ROM_BANK_N, RtcMode,
+                load_raw_ram, rom::Rom,
+            },
+            memory::{OPEN_BUS_VALUE, UNINIT_VALUE},
+        },
+        regions::{CART_RAM, MemoryRegion, ROM_SPACE},
+    },
+    region_guard,
+};
+use log::debug;
+
+const RAM_ENABLE: MemoryRegion = MemoryRegion::new(0x0000, 0x1FFF);
+const ROM_BANK_LOW: MemoryRegion = MemoryRegion::new(0x2000, 0x2FFF);
+const ROM_BANK_HIGH: MemoryRegion = MemoryRegion::new(0x3000, 0x3FFF);
+const RAM_BANK: MemoryRegion = MemoryRegion::new(0x4000, 0x5FFF);
+
+const RAM_ENABLE_VALUE: u8 = 0x0A;
+const RAM_BANK_MASK: u8 = 0x0F;
+
+// On rumble carts, bit 3 of the RAM bank register drives the motor instead
+const RUMBLE_RAM_BANK_MASK: u8 = 0x07;
+const RUMBLE_MOTOR_BIT: u8 = 0x08;
+
+#[derive(Debug)]
+pub struct CartMbc5 {
+    rom: Rom,
+    ram: Vec<u8>,
+    rom_banks: usize,
+    has_battery: bool,
+    has_rumble: bool,
+    ram_dirty: bool,
+
+    // Registers
+    ram_enabled: bool,
+    rom_bank: u16,
+    ram_bank: u8,
+
+    // Rumble
+    rumble_on: bool,
+    rumble_changed: bool,
+}
+
+impl CartMbc5 {
+    pub fn new(rom_banks: usize, ram_size: usize, has_battery: bool, has_rumble: bool) -> Self {
+        Self {
+            rom: Rom::default(),
+            ram: vec![UNINIT_VALUE; ram_size],
+            rom_banks,
+            has_battery,
+            has_rumble,
+            ram_dirty: false,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            rumble_on: false,
+            rumble_changed: false,
+        }
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        let offset =
+            self.ram_bank as usize * RAM_BANK_SIZE + CART_RAM.local_address(address) as usize;
+        offset % self.ram.len()
+    }
+
+    fn set_rumble(&mut self, on: bool) {
+        if on != self.rumble_on {
+            debug!("Rumble motor {}", if on { "on" } else { "off" });
+            self.rumble_on = on;
+            self.rumble_changed = true;
+        }
+    }
+}
+
+impl Cartridge for CartMbc5 {
+    fn init(&mut self) {
+        self.ram_enabled = false;
+        self.rom_bank = 1;
+        self.ram_bank = 0;
+        self.set_rumble(false);
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        region_guard!(address in ROM_SPACE);
+
+        if ROM_BANK_0.contains(address) {
+            self.rom.read(ROM_BANK_0.local_address(address) as usize)
+        } else {
+            // Unlike older MBCs, bank 0 can be mapped here too
+            self.rom
+                .read_bank(self.rom_bank as usize, ROM_BANK_N.local_address(address))
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        region_guard!(address in ROM_SPACE);
+
+        if RAM_ENABLE.contains(address) {
+            // MBC5 compares all 8 bits, not just the lower 4
+            self.ram_enabled = value == RAM_ENABLE_VALUE;
+        } else if ROM_BANK_LOW.contains(address) {
+            self.rom_bank = (self.rom_bank & 0x100) | value as u16;
+        } else if ROM_BANK_HIGH.contains(address) {
+            self.rom_bank = (self.rom_bank & 0xFF) | ((value as u16 & 0x01) << 8);
+        } else if RAM_BANK.contains(address) {
+            if self.has_rumble {
+                self.ram_bank = value & RUMBLE_RAM_BANK_MASK;
+                self.set_rumble(value & RUMBLE_MOTOR_BIT != 0);
+            } else {
+                self.ram_bank = value & RAM_BANK_MASK;
+            }
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        region_guard!(address in CART_RAM);
+
+        if self.ram_enabled && !self.ram.is_empty() {
+            self.ram[self.ram_offset(address)]
+        } else {
+            OPEN_BUS_VALUE
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        region_guard!(address in CART_RAM);
+
+        if self.ram_enabled && !self.ram.is_empty() {
+            let offset = self.ram_offset(address);
+            self.ram[offset] = value;
+            self.ram_dirty = true;
+        }
+    }
+
+    fn rumble_changed(&mut self) -> Option<bool> {
+        if self.rumble_changed {
+            self.rumble_changed = false;
+            Some(self.rumble_on)
+        } else {
+            None
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn load_save_data(&mut self, data: &[u8], rtc_mode: RtcMode) {
+        load_raw_ram(&mut self.ram, data);
+    }
+
+    fn save_changed(&mut self) -> bool {
+        std::mem::take(&mut self.ram_dirty)
+    }
+
+    fn load_from_bytes(&mut self, rom: &[u8]) -> Result<(), CartridgeError> {
+        self.rom = Rom::new(rom, self.rom_banks);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gb::hardware::cartridge::{ROM_BANK_SIZE, bank_numbered_rom};
+    use test_log::test;
+
+    /// Makes a cart where the first two bytes of every ROM bank are that bank's number.
+    fn make_cart(rom_banks: usize, ram_size: usize, has_rumble: bool) -> CartMbc5 {
+        let mut cart = CartMbc5::new(rom_banks, ram_size, false, has_rumble);
+        cart.load_from_bytes(&bank_numbered_rom(ROM_BANK_SIZE, rom_banks))
+            .unwrap();
+        cart.init();
+        cart
+    }
+
+    fn read_rom_bank(cart: &CartMbc5) -> usize {
+        cart.read_rom(0x4000) as usize | (cart.read_rom(0x4001) as usize) << 8
+    }
+
+    #[test]
+    fn test_rom_banking() {
+        let mut cart = make_cart(512, 0, false);
+        assert_eq!(read_rom_bank(&cart), 1);
+
+        cart.write_rom(0x2000, 0x00);
+        assert_eq!(read_rom_bank(&cart), 0);
+
+        cart.write_rom(0x3000, 0x01);
+        assert_eq!(read_rom_bank(&cart), 0x100);
+        cart.write_rom(0x2FFF, 0x23);
+        assert_eq!(read_rom_bank(&cart), 0x123);
+        cart.write_rom(0x3FFF, 0x00);
+        assert_eq!(read_rom_bank(&cart), 0x023);
+    }
+
+    #[test]
+    fn test_ram_banking() {
+        let mut cart = make_cart(2, 128 * 1024, false);
+
+        // Only exactly $0A enables RAM
+        cart.write_rom(0x0000, 0x1A);
+        assert!(!cart.ram_enabled);
+        cart.write_rom(0x0000, 0x0A);
+
+        for bank in 0..16 {
+            cart.write_rom(0x4000, bank);
+            cart.write_ram(0xA000, bank + 0x10);
+        }
+        for bank in 0..16 {
+            cart.write_rom(0x4000, bank);
+            assert_eq!(cart.read_ram(0xA000), bank + 0x10);
+        }
+    }
+
+    #[test]
+    fn test_rumble() {
+        let mut cart = make_cart(2, 32 * 1024, true);
+        cart.write_rom(0x0000, 0x0A);
+        assert_eq!(cart.rumble_changed(), None);
+
+        cart.write_rom(0x4000, 0x0B);
+        assert_eq!(cart.rumble_changed(), Some(true));
+        assert_eq!(cart.rumble_changed(), None);
+
+        // The motor bit doesn't select a RAM bank
+        cart.write_ram(0xA000, 0x42);
+        cart.write_rom(0x4000, 0x03);
+        assert_eq!(cart.read_ram(0xA000), 0x42);
+        assert_eq!(cart.rumble_changed(), Some(false));
+
+        // Writes that don't change the motor state aren't reported
+        cart.write_rom(0x4000, 0x01);
+        assert_eq!(cart.rumble_changed(), None);
+    }
+}
@@ -0,0 +1,292 @@
+use crate::{
+    gb::{
+        hardware::{
+            cartridge::{
+                Cartridge, CartridgeError, ROM_BANK_0, RtcMode,
+                flash::{FLASH_SIZE, Flash},
+                load_raw_ram,
+                rom::Rom,
+            },
+            memory::{OPEN_BUS_VALUE, UNINIT_VALUE},
+        },
+        regions::{CART_RAM, MemoryRegion, ROM_SPACE},
+    },
+    region_guard,
+};
+
+const RAM_ENABLE: MemoryRegion = MemoryRegion::new(0x0000, 0x03FF);
+const RAM_BANK_A: MemoryRegion = MemoryRegion::new(0x0400, 0x07FF);
+const RAM_BANK_B: MemoryRegion = MemoryRegion::new(0x0800, 0x0BFF);
+const FLASH_ENABLE: MemoryRegion = MemoryRegion::new(0x0C00, 0x0FFF);
+const FLASH_WRITE_ENABLE: MemoryRegion = MemoryRegion::new(0x1000, 0x1FFF);
+const ROM_BANK_A: MemoryRegion = MemoryRegion::new(0x2000, 0x27FF);
+const ROM_SELECT_A: MemoryRegion = MemoryRegion::new(0x2800, 0x2FFF);
+const ROM_BANK_B: MemoryRegion = MemoryRegion::new(0x3000, 0x37FF);
+const ROM_SELECT_B: MemoryRegion = MemoryRegion::new(0x3800, 0x3FFF);
+
+// The switchable halves of ROM and RAM space, each banked on its own
+const ROM_WINDOW_A: MemoryRegion = MemoryRegion::new(0x4000, 0x5FFF);
+const ROM_WINDOW_B: MemoryRegion = MemoryRegion::new(0x6000, 0x7FFF);
+const RAM_WINDOW_A: MemoryRegion = MemoryRegion::new(0xA000, 0xAFFF);
+
+const RAM_ENABLE_VALUE: u8 = 0x0A;
+const ROM_BANK_MASK: u8 = 0x7F;
+const RAM_BANK_MASK: u8 = 0x07;
+// Written to a select register to map the flash into that window instead of the ROM
+const SELECT_FLASH_BIT: u8 = 0x08;
+
+const WINDOW_ROM_BANK_SIZE: usize = 0x2000;
+const WINDOW_RAM_BANK_SIZE: usize = 0x1000;
+
+/// One of the two independently banked halves of each address range.
+#[derive(Debug, Default, Clone, Copy)]
+struct Window {
+    rom_bank: u8,
+    flash_selected: bool,
+    ram_bank: u8,
+}
+
+/// The MBC6, used only by Net de Get: Minigame @ 100. ROM space above $4000 and the RAM space
+/// are each split into two 8 KiB/4 KiB windows with their own bank, and either ROM window can
+/// show the 1 MiB flash chip instead of the ROM.
+#[derive(Debug)]
+pub struct CartMbc6 {
+    rom: Rom,
+    ram: Vec<u8>,
+    flash: Flash,
+    rom_banks: usize,
+    ram_dirty: bool,
+
+    // Registers
+    ram_enabled: bool,
+    flash_enabled: bool,
+    flash_write_enabled: bool,
+    windows: [Window; 2],
+}
+
+impl CartMbc6 {
+    /// The MBC6 always has a battery, which keeps both the RAM and the flash.
+    pub fn new(rom_banks: usize, ram_size: usize) -> Self {
+        Self {
+            rom: Rom::default(),
+            ram: vec![UNINIT_VALUE; ram_size],
+            flash: Flash::new(FLASH_SIZE),
+            rom_banks,
+            ram_dirty: false,
+            ram_enabled: false,
+            flash_enabled: false,
+            flash_write_enabled: false,
+            windows: [Window::default(); 2],
+        }
+    }
+
+    fn rom_window(address: u16) -> (usize, u16) {
+        if ROM_WINDOW_A.contains(address) {
+            (0, ROM_WINDOW_A.local_address(address))
+        } else {
+            (1, ROM_WINDOW_B.local_address(address))
+        }
+    }
+
+    fn flash_offset(&self, window: usize, local_address: u16) -> Option<usize> {
+        let window = self.windows[window];
+        (window.flash_selected && self.flash_enabled)
+            .then(|| window.rom_bank as usize * WINDOW_ROM_BANK_SIZE + local_address as usize)
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        let local = CART_RAM.local_address(address) as usize;
+        let (window, local) = if RAM_WINDOW_A.contains(address) {
+            (0, local)
+        } else {
+            (1, local - WINDOW_RAM_BANK_SIZE)
+        };
+        (self.windows[window].ram_bank as usize * WINDOW_RAM_BANK_SIZE + local) % self.ram.len()
+    }
+}
+
+impl Cartridge for CartMbc6 {
+    fn init(&mut self) {
+        self.ram_enabled = false;
+        self.flash_enabled = false;
+        self.flash_write_enabled = false;
+        self.windows = [Window::default(); 2];
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        region_guard!(address in ROM_SPACE);
+
+        if ROM_BANK_0.contains(address) {
+            return self.rom.read(ROM_BANK_0.local_address(address) as usize);
+        }
+
+        let (window, local) = Self::rom_window(address);
+        if self.windows[window].flash_selected {
+            match self.flash_offset(window, local) {
+                Some(offset) => self.flash.read(offset),
+                None => OPEN_BUS_VALUE,
+            }
+        } else {
+            let bank = self.windows[window].rom_bank as usize;
+            self.rom.read(bank * WINDOW_ROM_BANK_SIZE + local as usize)
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        region_guard!(address in ROM_SPACE);
+
+        if RAM_ENABLE.contains(address) {
+            self.ram_enabled = (value & 0x0F) == RAM_ENABLE_VALUE;
+        } else if RAM_BANK_A.contains(address) {
+            self.windows[0].ram_bank = value & RAM_BANK_MASK;
+        } else if RAM_BANK_B.contains(address) {
+            self.windows[1].ram_bank = value & RAM_BANK_MASK;
+        } else if FLASH_ENABLE.contains(address) {
+            self.flash_enabled = value & 0x01 != 0;
+        } else if FLASH_WRITE_ENABLE.contains(address) {
+            self.flash_write_enabled = value & 0x01 != 0;
+        } else if ROM_BANK_A.contains(address) {
+            self.windows[0].rom_bank = value & ROM_BANK_MASK;
+        } else if ROM_SELECT_A.contains(address) {
+            self.windows[0].flash_selected = value & SELECT_FLASH_BIT != 0;
+        } else if ROM_BANK_B.contains(address) {
+            self.windows[1].rom_bank = value & ROM_BANK_MASK;
+        } else if ROM_SELECT_B.contains(address) {
+            self.windows[1].flash_selected = value & SELECT_FLASH_BIT != 0;
+        } else if self.flash_write_enabled {
+            // Writes to a window showing the flash go to its command interface
+            let (window, local) = Self::rom_window(address);
+            if let Some(offset) = self.flash_offset(window, local) {
+                self.ram_dirty |= self.flash.write(offset, value);
+            }
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        region_guard!(address in CART_RAM);
+
+        if self.ram_enabled && !self.ram.is_empty() {
+            self.ram[self.ram_offset(address)]
+        } else {
+            OPEN_BUS_VALUE
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        region_guard!(address in CART_RAM);
+
+        if self.ram_enabled && !self.ram.is_empty() {
+            let offset = self.ram_offset(address);
+            self.ram[offset] = value;
+            self.ram_dirty = true;
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    /// The RAM followed by the whole flash chip, like mGBA.
+    fn save_data(&self) -> Vec<u8> {
+        [self.ram.as_slice(), self.flash.data()].concat()
+    }
+
+    fn load_save_data(&mut self, data: &[u8], rtc_mode: RtcMode) {
+        let (ram, flash) = data.split_at(data.len().min(self.ram.len()));
+        load_raw_ram(&mut self.ram, ram);
+        if !flash.is_empty() {
+            load_raw_ram(self.flash.data_mut(), flash);
+        }
+    }
+
+    fn save_changed(&mut self) -> bool {
+        std::mem::take(&mut self.ram_dirty)
+    }
+
+    fn load_from_bytes(&mut self, rom: &[u8]) -> Result<(), CartridgeError> {
+        self.rom = Rom::new(rom, self.rom_banks);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gb::hardware::cartridge::bank_numbered_rom;
+    use test_log::test;
+
+    /// Makes a cart where the first byte of every 8 KiB ROM bank is that bank's number.
+    fn make_cart() -> CartMbc6 {
+        let mut cart = CartMbc6::new(64, 32 * 1024);
+        cart.load_from_bytes(&bank_numbered_rom(WINDOW_ROM_BANK_SIZE, 128))
+            .unwrap();
+        cart.init();
+        cart
+    }
+
+    #[test]
+    fn test_rom_windows() {
+        let mut cart = make_cart();
+        cart.write_rom(0x2000, 0x05);
+        cart.write_rom(0x3000, 0x7F);
+        assert_eq!(cart.read_rom(0x4000), 0x05);
+        assert_eq!(cart.read_rom(0x6000), 0x7F);
+        assert_eq!(cart.read_rom(0x0000), 0x00);
+    }
+
+    #[test]
+    fn test_ram_windows() {
+        let mut cart = make_cart();
+        cart.write_rom(0x0000, RAM_ENABLE_VALUE);
+        cart.write_rom(0x0400, 0x02);
+        cart.write_rom(0x0800, 0x07);
+        cart.write_ram(0xA000, 0x12);
+        cart.write_ram(0xB000, 0x34);
+        assert_eq!(cart.ram[2 * WINDOW_RAM_BANK_SIZE], 0x12);
+        assert_eq!(cart.ram[7 * WINDOW_RAM_BANK_SIZE], 0x34);
+
+        cart.write_rom(0x0800, 0x02);
+        assert_eq!(cart.read_ram(0xB000), 0x12);
+    }
+
+    #[test]
+    fn test_flash() {
+        let mut cart = make_cart();
+        cart.write_rom(0x0C00, 0x01);
+        cart.write_rom(0x1000, 0x01);
+        cart.write_rom(0x2800, SELECT_FLASH_BIT);
+        cart.write_rom(0x3800, SELECT_FLASH_BIT);
+        assert_eq!(cart.read_rom(0x4000), 0xFF);
+
+        // The unlock addresses land in flash banks 2 and 1
+        cart.write_rom(0x2000, 0x02);
+        cart.write_rom(0x3000, 0x01);
+        cart.write_rom(0x5555, 0xAA);
+        cart.write_rom(0x6AAA, 0x55);
+        cart.write_rom(0x5555, 0xA0);
+        cart.write_rom(0x2000, 0x10);
+        cart.write_rom(0x4123, 0x42);
+        assert_eq!(cart.read_rom(0x4123), 0x42);
+        assert!(cart.save_changed());
+
+        // The flash is saved after the RAM
+        let data = cart.save_data();
+        assert_eq!(data.len(), 32 * 1024 + FLASH_SIZE);
+        assert_eq!(data[32 * 1024 + 0x10 * WINDOW_ROM_BANK_SIZE + 0x123], 0x42);
+
+        let mut loaded = make_cart();
+        loaded.load_save_data(&data, RtcMode::Frozen);
+        loaded.write_rom(0x0C00, 0x01);
+        loaded.write_rom(0x2800, SELECT_FLASH_BIT);
+        loaded.write_rom(0x2000, 0x10);
+        assert_eq!(loaded.read_rom(0x4123), 0x42);
+
+        // Without write enable, the flash ignores commands
+        cart.write_rom(0x1000, 0x00);
+        cart.write_rom(0x2000, 0x02);
+        cart.write_rom(0x5555, 0xAA);
+        cart.write_rom(0x6AAA, 0x55);
+        cart.write_rom(0x5555, 0x90);
+        assert_eq!(cart.read_rom(0x4000), 0xFF);
+    }
+}
@@ -0,0 +1,502 @@
+use crate::{
+    gb::{
+        hardware::{
+            cartridge::{
+                Cartridge, CartridgeError, ROM_BANK_0, ROM_BANK_N, RtcMode, load_raw_ram, rom::Rom,
+            },
+            memory::OPEN_BUS_VALUE,
+        },
+        regions::{CART_RAM, MemoryRegion, ROM_SPACE},
+    },
+    region_guard,
+};
+use log::debug;
+
+const RAM_ENABLE_1: MemoryRegion = MemoryRegion::new(0x0000, 0x1FFF);
+const ROM_BANK: MemoryRegion = MemoryRegion::new(0x2000, 0x3FFF);
+const RAM_ENABLE_2: MemoryRegion = MemoryRegion::new(0x4000, 0x5FFF);
+
+// Only the first half of CART_RAM is wired up, with address bits 4-7 selecting a register
+const REGISTERS: MemoryRegion = MemoryRegion::new(0xA000, 0xAFFF);
+
+const RAM_ENABLE_1_VALUE: u8 = 0x0A;
+const RAM_ENABLE_2_VALUE: u8 = 0x40;
+const ROM_BANK_MASK: u8 = 0x7F;
+
+// Registers
+const REG_ERASE: u16 = 0x0;
+const REG_LATCH: u16 = 0x1;
+const REG_X_LOW: u16 = 0x2;
+const REG_X_HIGH: u16 = 0x3;
+const REG_Y_LOW: u16 = 0x4;
+const REG_Y_HIGH: u16 = 0x5;
+const REG_ZERO: u16 = 0x6;
+const REG_EEPROM: u16 = 0x8;
+
+const ERASE_VALUE: u8 = 0x55;
+const LATCH_VALUE: u8 = 0xAA;
+
+// Accelerometer readings: flat is $81D0, and each g of tilt moves that by about $70
+const TILT_ERASED: u16 = 0x8000;
+const TILT_CENTER: f32 = 0x81D0 as f32;
+const TILT_PER_G: f32 = 0x70 as f32;
+
+// Bits in REG_EEPROM
+const EEPROM_CS: u8 = 0x80;
+const EEPROM_CLK: u8 = 0x40;
+const EEPROM_DI: u8 = 0x02;
+const EEPROM_DO: u8 = 0x01;
+
+// The 93LC56 is organised as 128 16-bit words
+const EEPROM_WORDS: usize = 128;
+const EEPROM_ADDRESS_MASK: u8 = 0x7F;
+
+// Start bit, then a 2-bit opcode and an 8-bit address
+const EEPROM_COMMAND_BITS: u8 = 10;
+const EEPROM_DATA_BITS: u8 = 16;
+
+/// What the EEPROM does with the next bits clocked in or out.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum EepromState {
+    /// Waiting for a start bit.
+    #[default]
+    Idle,
+    /// Shifting in the opcode and address.
+    Command,
+    /// Shifting out a word (after a dummy 0 bit), then carrying on with the next one.
+    Read { address: u8 },
+    /// Shifting in a word to write to `address`, or to every address if it's None.
+    Write { address: Option<u8> },
+}
+
+/// A Microchip 93LC56 serial EEPROM, bit-banged by the game through REG_EEPROM.
+#[derive(Debug)]
+struct Eeprom {
+    words: [u16; EEPROM_WORDS],
+    write_enabled: bool,
+    dirty: bool,
+
+    // Pins, as last written by the game
+    cs: bool,
+    clk: bool,
+    di: bool,
+    data_out: bool,
+
+    state: EepromState,
+    shift: u16,
+    bits: u8,
+}
+
+impl Default for Eeprom {
+    fn default() -> Self {
+        Self {
+            // Blank EEPROMs are all 1s
+            words: [0xFFFF; EEPROM_WORDS],
+            write_enabled: false,
+            dirty: false,
+            cs: false,
+            clk: false,
+            di: false,
+            data_out: true,
+            state: EepromState::Idle,
+            shift: 0,
+            bits: 0,
+        }
+    }
+}
+
+impl Eeprom {
+    fn read_pins(&self) -> u8 {
+        let mut value = 0;
+        if self.cs {
+            value |= EEPROM_CS;
+        }
+        if self.clk {
+            value |= EEPROM_CLK;
+        }
+        if self.di {
+            value |= EEPROM_DI;
+        }
+        if self.data_out {
+            value |= EEPROM_DO;
+        }
+        value
+    }
+
+    fn write_pins(&mut self, value: u8) {
+        let cs = value & EEPROM_CS != 0;
+        let clk = value & EEPROM_CLK != 0;
+        self.di = value & EEPROM_DI != 0;
+
+        if !cs {
+            // Deselecting aborts whatever was going on
+            self.state = EepromState::Idle;
+        } else if clk && !self.clk {
+            self.clock_in(self.di);
+        }
+
+        self.cs = cs;
+        self.clk = clk;
+    }
+
+    /// Handles a rising clock edge.
+    fn clock_in(&mut self, bit: bool) {
+        match self.state {
+            EepromState::Idle => {
+                if bit {
+                    self.state = EepromState::Command;
+                    self.shift = 0;
+                    self.bits = 0;
+                }
+            }
+            EepromState::Command => {
+                self.shift_in(bit);
+                if self.bits == EEPROM_COMMAND_BITS {
+                    self.run_command();
+                }
+            }
+            EepromState::Read { address } => {
+                // Words come out MSB first
+                self.data_out = self.shift & 0x8000 != 0;
+                self.shift <<= 1;
+                self.bits += 1;
+                if self.bits == EEPROM_DATA_BITS {
+                    // Sequential reads roll over into the next word
+                    let next = address.wrapping_add(1) & EEPROM_ADDRESS_MASK;
+                    self.state = EepromState::Read { address: next };
+                    self.shift = self.words[next as usize];
+                    self.bits = 0;
+                }
+            }
+            EepromState::Write { address } => {
+                self.shift_in(bit);
+                if self.bits == EEPROM_DATA_BITS {
+                    match address {
+                        Some(address) => self.write_word(address, self.shift),
+                        None => {
+                            for address in 0..EEPROM_WORDS as u8 {
+                                self.write_word(address, self.shift);
+                            }
+                        }
+                    }
+                    self.state = EepromState::Idle;
+                }
+            }
+        }
+    }
+
+    fn shift_in(&mut self, bit: bool) {
+        self.shift = (self.shift << 1) | bit as u16;
+        self.bits += 1;
+    }
+
+    fn run_command(&mut self) {
+        let opcode = (self.shift >> 8) & 0x03;
+        let address = self.shift as u8;
+        self.shift = 0;
+        self.bits = 0;
+        self.state = EepromState::Idle;
+
+        match (opcode, address >> 6) {
+            // READ: a dummy 0 comes out first
+            (0b10, _) => {
+                let address = address & EEPROM_ADDRESS_MASK;
+                self.data_out = false;
+                self.shift = self.words[address as usize];
+                self.state = EepromState::Read { address };
+            }
+            // WRITE
+            (0b01, _) => {
+                self.state = EepromState::Write {
+                    address: Some(address & EEPROM_ADDRESS_MASK),
+                }
+            }
+            // ERASE
+            (0b11, _) => self.write_word(address & EEPROM_ADDRESS_MASK, 0xFFFF),
+            // EWDS
+            (0b00, 0b00) => self.write_enabled = false,
+            // WRAL
+            (0b00, 0b01) => self.state = EepromState::Write { address: None },
+            // ERAL
+            (0b00, 0b10) => {
+                for address in 0..EEPROM_WORDS as u8 {
+                    self.write_word(address, 0xFFFF);
+                }
+            }
+            // EWEN
+            _ => self.write_enabled = true,
+        }
+    }
+
+    fn write_word(&mut self, address: u8, value: u16) {
+        if self.write_enabled {
+            self.words[address as usize] = value;
+            self.dirty = true;
+        } else {
+            debug!("Ignoring MBC7 EEPROM write while write protected");
+        }
+        // Writes finish instantly, so always report ready
+        self.data_out = true;
+    }
+}
+
+#[derive(Debug)]
+pub struct CartMbc7 {
+    rom: Rom,
+    rom_banks: usize,
+    eeprom: Eeprom,
+
+    // Registers
+    ram_enabled_1: bool,
+    ram_enabled_2: bool,
+    rom_bank: u8,
+
+    // Accelerometer
+    tilt_x: u16,
+    tilt_y: u16,
+    latched_x: u16,
+    latched_y: u16,
+    latch_erased: bool,
+}
+
+impl CartMbc7 {
+    pub fn new(rom_banks: usize) -> Self {
+        Self {
+            rom: Rom::default(),
+            rom_banks,
+            eeprom: Eeprom::default(),
+            ram_enabled_1: false,
+            ram_enabled_2: false,
+            rom_bank: 1,
+            tilt_x: TILT_CENTER as u16,
+            tilt_y: TILT_CENTER as u16,
+            latched_x: TILT_ERASED,
+            latched_y: TILT_ERASED,
+            latch_erased: false,
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled_1 && self.ram_enabled_2
+    }
+
+    fn register(address: u16) -> u16 {
+        (REGISTERS.local_address(address) >> 4) & 0x0F
+    }
+
+    fn tilt_reading(g: f32) -> u16 {
+        (TILT_CENTER + g * TILT_PER_G).clamp(0.0, u16::MAX as f32) as u16
+    }
+}
+
+impl Cartridge for CartMbc7 {
+    fn init(&mut self) {
+        self.ram_enabled_1 = false;
+        self.ram_enabled_2 = false;
+        self.rom_bank = 1;
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        region_guard!(address in ROM_SPACE);
+
+        if ROM_BANK_0.contains(address) {
+            self.rom.read(ROM_BANK_0.local_address(address) as usize)
+        } else {
+            self.rom
+                .read_bank(self.rom_bank as usize, ROM_BANK_N.local_address(address))
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        region_guard!(address in ROM_SPACE);
+
+        if RAM_ENABLE_1.contains(address) {
+            self.ram_enabled_1 = value == RAM_ENABLE_1_VALUE;
+        } else if ROM_BANK.contains(address) {
+            self.rom_bank = value & ROM_BANK_MASK;
+        } else if RAM_ENABLE_2.contains(address) {
+            self.ram_enabled_2 = value == RAM_ENABLE_2_VALUE;
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        region_guard!(address in CART_RAM);
+
+        if !self.ram_enabled() || !REGISTERS.contains(address) {
+            return OPEN_BUS_VALUE;
+        }
+
+        match Self::register(address) {
+            REG_X_LOW => self.latched_x as u8,
+            REG_X_HIGH => (self.latched_x >> 8) as u8,
+            REG_Y_LOW => self.latched_y as u8,
+            REG_Y_HIGH => (self.latched_y >> 8) as u8,
+            REG_ZERO => 0x00,
+            REG_EEPROM => self.eeprom.read_pins(),
+            _ => OPEN_BUS_VALUE,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        region_guard!(address in CART_RAM);
+
+        if !self.ram_enabled() || !REGISTERS.contains(address) {
+            return;
+        }
+
+        match Self::register(address) {
+            REG_ERASE if value == ERASE_VALUE => {
+                self.latched_x = TILT_ERASED;
+                self.latched_y = TILT_ERASED;
+                self.latch_erased = true;
+            }
+            // Latching only works once the previous reading has been erased
+            REG_LATCH if value == LATCH_VALUE && self.latch_erased => {
+                self.latched_x = self.tilt_x;
+                self.latched_y = self.tilt_y;
+                self.latch_erased = false;
+            }
+            REG_EEPROM => self.eeprom.write_pins(value),
+            _ => (),
+        }
+    }
+
+    fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt_x = Self::tilt_reading(x);
+        self.tilt_y = Self::tilt_reading(y);
+    }
+
+    fn has_battery(&self) -> bool {
+        // Not really a battery, but the EEPROM keeps its contents the same way
+        true
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.eeprom
+            .words
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+
+    fn load_save_data(&mut self, data: &[u8], rtc_mode: RtcMode) {
+        let mut bytes = [0; EEPROM_WORDS * 2];
+        bytes.copy_from_slice(&self.save_data());
+        load_raw_ram(&mut bytes, data);
+        for (word, pair) in self.eeprom.words.iter_mut().zip(bytes.chunks_exact(2)) {
+            *word = u16::from_le_bytes([pair[0], pair[1]]);
+        }
+    }
+
+    fn save_changed(&mut self) -> bool {
+        std::mem::take(&mut self.eeprom.dirty)
+    }
+
+    fn load_from_bytes(&mut self, rom: &[u8]) -> Result<(), CartridgeError> {
+        self.rom = Rom::new(rom, self.rom_banks);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gb::hardware::cartridge::ROM_BANK_SIZE;
+    use test_log::test;
+
+    fn make_cart() -> CartMbc7 {
+        let mut cart = CartMbc7::new(4);
+        cart.load_from_bytes(&vec![0; 4 * ROM_BANK_SIZE]).unwrap();
+        cart.init();
+        cart.write_rom(0x0000, RAM_ENABLE_1_VALUE);
+        cart.write_rom(0x4000, RAM_ENABLE_2_VALUE);
+        cart
+    }
+
+    /// Clocks bits into the EEPROM, MSB first, returning what DO read after each one.
+    fn clock_bits(cart: &mut CartMbc7, value: u32, count: u32) -> u32 {
+        let mut out = 0;
+        for i in (0..count).rev() {
+            let di = if (value >> i) & 1 != 0 { EEPROM_DI } else { 0 };
+            cart.write_ram(0xA080, EEPROM_CS | di);
+            cart.write_ram(0xA080, EEPROM_CS | EEPROM_CLK | di);
+            out = (out << 1) | (cart.read_ram(0xA080) & EEPROM_DO) as u32;
+        }
+        out
+    }
+
+    fn command(cart: &mut CartMbc7, opcode: u32, address: u32) {
+        cart.write_ram(0xA080, 0x00);
+        clock_bits(cart, (0b1 << 10) | (opcode << 8) | address, 11);
+    }
+
+    #[test]
+    fn test_accelerometer() {
+        let mut cart = make_cart();
+        cart.set_tilt(1.0, -0.5);
+
+        // Latching does nothing until the old values are erased
+        cart.write_ram(0xA010, LATCH_VALUE);
+        assert_eq!(cart.read_ram(0xA020), 0x00);
+        assert_eq!(cart.read_ram(0xA030), 0x80);
+
+        cart.write_ram(0xA000, ERASE_VALUE);
+        cart.write_ram(0xA010, LATCH_VALUE);
+        let x = cart.read_ram(0xA020) as u16 | (cart.read_ram(0xA030) as u16) << 8;
+        let y = cart.read_ram(0xA040) as u16 | (cart.read_ram(0xA050) as u16) << 8;
+        assert_eq!(x, 0x81D0 + 0x70);
+        assert_eq!(y, 0x81D0 - 0x38);
+
+        // The latch holds its values
+        cart.set_tilt(0.0, 0.0);
+        assert_eq!(cart.read_ram(0xA020), 0x40);
+    }
+
+    #[test]
+    fn test_ram_enable() {
+        let mut cart = make_cart();
+        cart.write_rom(0x4000, 0x00);
+        assert_eq!(cart.read_ram(0xA060), OPEN_BUS_VALUE);
+        cart.write_rom(0x4000, RAM_ENABLE_2_VALUE);
+        assert_eq!(cart.read_ram(0xA060), 0x00);
+        assert_eq!(cart.read_ram(0xB060), OPEN_BUS_VALUE);
+    }
+
+    #[test]
+    fn test_eeprom() {
+        let mut cart = make_cart();
+
+        // Write protected by default
+        command(&mut cart, 0b01, 0x05);
+        clock_bits(&mut cart, 0x1234, 16);
+        assert_eq!(cart.eeprom.words[5], 0xFFFF);
+
+        // EWEN, then WRITE
+        command(&mut cart, 0b00, 0xC0);
+        command(&mut cart, 0b01, 0x05);
+        clock_bits(&mut cart, 0x1234, 16);
+        assert_eq!(cart.eeprom.words[5], 0x1234);
+        assert!(cart.save_changed());
+
+        // READ: a dummy 0, then the word, then the next one
+        cart.eeprom.words[6] = 0xBEEF;
+        command(&mut cart, 0b10, 0x05);
+        assert_eq!(cart.read_ram(0xA080) & EEPROM_DO, 0);
+        assert_eq!(clock_bits(&mut cart, 0, 16), 0x1234);
+        assert_eq!(clock_bits(&mut cart, 0, 16), 0xBEEF);
+
+        // ERASE, then ERAL
+        command(&mut cart, 0b11, 0x05);
+        assert_eq!(cart.eeprom.words[5], 0xFFFF);
+        command(&mut cart, 0b00, 0x80);
+        assert_eq!(cart.eeprom.words[6], 0xFFFF);
+
+        // Saves are the words in little endian order
+        cart.eeprom.words[0] = 0xABCD;
+        let data = cart.save_data();
+        assert_eq!(&data[..2], &[0xCD, 0xAB]);
+        let mut loaded = make_cart();
+        loaded.load_save_data(&data, RtcMode::Frozen);
+        assert_eq!(loaded.eeprom.words[0], 0xABCD);
+    }
+}
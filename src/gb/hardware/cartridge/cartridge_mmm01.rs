@@ -0,0 +1,337 @@
+use crate::{
+    gb::{
+        hardware::{
+            cartridge::{
+                Cartridge, CartridgeError, RAM_BANK_SIZE, ROM_BANK_0, ROM_BANK_N, RtcMode,
+                load_raw_ram, rom::Rom,
+            },
+            memory::{OPEN_BUS_VALUE, UNINIT_VALUE},
+        },
+        regions::{CART_RAM, MemoryRegion, ROM_SPACE},
+    },
+    region_guard,
+};
+use log::debug;
+
+const RAM_ENABLE: MemoryRegion = MemoryRegion::new(0x0000, 0x1FFF);
+const ROM_BANK: MemoryRegion = MemoryRegion::new(0x2000, 0x3FFF);
+const RAM_BANK: MemoryRegion = MemoryRegion::new(0x4000, 0x5FFF);
+const BANKING_MODE: MemoryRegion = MemoryRegion::new(0x6000, 0x7FFF);
+
+const RAM_ENABLE_VALUE: u8 = 0x0A;
+const ROM_BANK_MASK: u8 = 0x1F;
+const RAM_BANK_MASK: u8 = 0x03;
+const MAP_ENABLE_BIT: u8 = 0x40;
+const MODE_LOCK_BIT: u8 = 0x40;
+const MULTIPLEX_BIT: u8 = 0x40;
+
+// Until the menu locks in a game, every bank bit is pulled high, so the last 32 KiB is mapped.
+// The ROM mirrors these down to its actual size.
+const MENU_BANK_0: usize = 0x1FE;
+const MENU_BANK_N: usize = 0x1FF;
+
+/// The MMM01 wraps an MBC1-style mapper for multi-game compilations. The menu picks a game by
+/// writing the outer bank bits and masks, then locks them in; from then on the game sees a plain
+/// MBC1 confined to its own ROM/RAM window.
+#[derive(Debug)]
+pub struct CartMmm01 {
+    rom: Rom,
+    ram: Vec<u8>,
+    rom_banks: usize,
+    has_battery: bool,
+    ram_dirty: bool,
+
+    // Registers
+    mapped: bool,
+    ram_enabled: bool,
+    rom_bank_low: u8,
+    rom_bank_mid: u8,
+    rom_bank_high: u8,
+    ram_bank_low: u8,
+    ram_bank_high: u8,
+    /// Set bits of the ROM bank (bits 1-4) the game can't change once mapped.
+    rom_mask: u8,
+    /// Set bits of the RAM bank (bits 0-1) the game can't change once mapped.
+    ram_mask: u8,
+    advanced_banking: bool,
+    mode_locked: bool,
+    /// Swaps the roles of the RAM bank bits and the middle ROM bank bits.
+    multiplex: bool,
+}
+
+impl CartMmm01 {
+    pub fn new(rom_banks: usize, ram_size: usize, has_battery: bool) -> Self {
+        Self {
+            rom: Rom::default(),
+            ram: vec![UNINIT_VALUE; ram_size],
+            rom_banks,
+            has_battery,
+            ram_dirty: false,
+            mapped: false,
+            ram_enabled: false,
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            rom_mask: 0,
+            ram_mask: 0,
+            advanced_banking: false,
+            mode_locked: false,
+            multiplex: false,
+        }
+    }
+
+    /// Once mapped, only the unmasked bits of `current` take the bits of `value`.
+    fn masked_write(&self, current: u8, value: u8, mask: u8) -> u8 {
+        if self.mapped {
+            (current & mask) | (value & !mask)
+        } else {
+            value
+        }
+    }
+
+    /// Like MBC1's secondary register, the game's RAM bank bits only reach the address lines in
+    /// advanced banking mode; the bits fixed by the menu always do.
+    fn ram_bank_bits(&self) -> u8 {
+        if self.advanced_banking {
+            self.ram_bank_low
+        } else {
+            self.ram_bank_low & self.ram_mask
+        }
+    }
+
+    fn outer_rom_bank(&self, rom_bank_mid: u8) -> usize {
+        ((self.rom_bank_high as usize) << 7) | ((rom_bank_mid as usize) << 5)
+    }
+
+    fn rom_bank_0(&self) -> usize {
+        if !self.mapped {
+            return MENU_BANK_0;
+        }
+        let mid = if self.multiplex {
+            self.ram_bank_bits()
+        } else {
+            self.rom_bank_mid
+        };
+        self.outer_rom_bank(mid) | (self.rom_bank_low & self.rom_mask) as usize
+    }
+
+    fn rom_bank_n(&self) -> usize {
+        if !self.mapped {
+            return MENU_BANK_N;
+        }
+        let mid = if self.multiplex {
+            self.ram_bank_low
+        } else {
+            self.rom_bank_mid
+        };
+        // The MBC1 0 -> 1 quirk only looks at the bits the game controls
+        let mut low = self.rom_bank_low;
+        if low & !self.rom_mask & ROM_BANK_MASK == 0 {
+            low |= 0x01;
+        }
+        self.outer_rom_bank(mid) | low as usize
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        let low = if self.multiplex {
+            self.rom_bank_mid
+        } else {
+            self.ram_bank_bits()
+        };
+        let bank = ((self.ram_bank_high as usize) << 2) | low as usize;
+        (bank * RAM_BANK_SIZE + CART_RAM.local_address(address) as usize) % self.ram.len()
+    }
+}
+
+impl Cartridge for CartMmm01 {
+    fn init(&mut self) {
+        self.mapped = false;
+        self.ram_enabled = false;
+        self.rom_bank_low = 0;
+        self.rom_bank_mid = 0;
+        self.rom_bank_high = 0;
+        self.ram_bank_low = 0;
+        self.ram_bank_high = 0;
+        self.rom_mask = 0;
+        self.ram_mask = 0;
+        self.advanced_banking = false;
+        self.mode_locked = false;
+        self.multiplex = false;
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        region_guard!(address in ROM_SPACE);
+
+        if ROM_BANK_0.contains(address) {
+            self.rom
+                .read_bank(self.rom_bank_0(), ROM_BANK_0.local_address(address))
+        } else {
+            self.rom
+                .read_bank(self.rom_bank_n(), ROM_BANK_N.local_address(address))
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        region_guard!(address in ROM_SPACE);
+
+        // Everything but the MBC1 bits is only writable until the mapping is locked in
+        if RAM_ENABLE.contains(address) {
+            self.ram_enabled = (value & 0x0F) == RAM_ENABLE_VALUE;
+            if !self.mapped {
+                self.ram_mask = (value >> 4) & RAM_BANK_MASK;
+                if value & MAP_ENABLE_BIT != 0 {
+                    self.mapped = true;
+                    debug!(
+                        "MMM01 mapped: ROM bank 0 = {}, RAM bank = {}",
+                        self.rom_bank_0(),
+                        ((self.ram_bank_high as usize) << 2) | self.ram_bank_low as usize
+                    );
+                }
+            }
+        } else if ROM_BANK.contains(address) {
+            self.rom_bank_low =
+                self.masked_write(self.rom_bank_low, value & ROM_BANK_MASK, self.rom_mask);
+            if !self.mapped {
+                self.rom_bank_mid = (value >> 5) & 0x03;
+            }
+        } else if RAM_BANK.contains(address) {
+            self.ram_bank_low =
+                self.masked_write(self.ram_bank_low, value & RAM_BANK_MASK, self.ram_mask);
+            if !self.mapped {
+                self.ram_bank_high = (value >> 2) & 0x03;
+                self.rom_bank_high = (value >> 4) & 0x03;
+                self.mode_locked = value & MODE_LOCK_BIT != 0;
+            }
+        } else if BANKING_MODE.contains(address) {
+            if !(self.mapped && self.mode_locked) {
+                self.advanced_banking = (value & 0x01) != 0;
+            }
+            if !self.mapped {
+                self.rom_mask = (value & 0x3C) >> 1;
+                self.multiplex = value & MULTIPLEX_BIT != 0;
+            }
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        region_guard!(address in CART_RAM);
+
+        if self.ram_enabled && !self.ram.is_empty() {
+            self.ram[self.ram_offset(address)]
+        } else {
+            OPEN_BUS_VALUE
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        region_guard!(address in CART_RAM);
+
+        if self.ram_enabled && !self.ram.is_empty() {
+            let offset = self.ram_offset(address);
+            self.ram[offset] = value;
+            self.ram_dirty = true;
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn load_save_data(&mut self, data: &[u8], rtc_mode: RtcMode) {
+        load_raw_ram(&mut self.ram, data);
+    }
+
+    fn save_changed(&mut self) -> bool {
+        std::mem::take(&mut self.ram_dirty)
+    }
+
+    fn load_from_bytes(&mut self, rom: &[u8]) -> Result<(), CartridgeError> {
+        self.rom = Rom::new(rom, self.rom_banks);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gb::hardware::cartridge::{ROM_BANK_SIZE, bank_numbered_rom};
+    use test_log::test;
+
+    /// Makes a cart where the first byte of every ROM bank is that bank's number.
+    fn make_cart(rom_banks: usize, ram_size: usize) -> CartMmm01 {
+        let mut cart = CartMmm01::new(rom_banks, ram_size, true);
+        cart.load_from_bytes(&bank_numbered_rom(ROM_BANK_SIZE, rom_banks))
+            .unwrap();
+        cart.init();
+        cart
+    }
+
+    #[test]
+    fn test_menu() {
+        let mut cart = make_cart(64, 32 * 1024);
+        assert_eq!(cart.read_rom(0x0000), 62);
+        assert_eq!(cart.read_rom(0x4000), 63);
+
+        // Bank writes don't move the menu until it's mapped
+        cart.write_rom(0x2000, 0x05);
+        assert_eq!(cart.read_rom(0x4000), 63);
+
+        cart.init();
+        cart.write_rom(0x0000, MAP_ENABLE_BIT);
+        assert_eq!(cart.read_rom(0x0000), 0);
+        assert_eq!(cart.read_rom(0x4000), 1);
+    }
+
+    #[test]
+    fn test_game_window() {
+        let mut cart = make_cart(64, 32 * 1024);
+
+        // A 128 KiB game at bank 0x28: banks 0x20 + 0x08, with ROM bits 3-4 fixed
+        cart.write_rom(0x2000, 0x20 | 0x08);
+        cart.write_rom(0x6000, 0x18 << 1);
+        // RAM bank 2, fixed
+        cart.write_rom(0x4000, 0x02);
+        cart.write_rom(0x0000, MAP_ENABLE_BIT | 0x30);
+
+        assert_eq!(cart.read_rom(0x0000), 0x28);
+        assert_eq!(cart.read_rom(0x4000), 0x29);
+        cart.write_rom(0x2000, 0x03);
+        assert_eq!(cart.read_rom(0x4000), 0x2B);
+        cart.write_rom(0x2000, 0x1F);
+        assert_eq!(cart.read_rom(0x4000), 0x2F);
+
+        // The outer bits can't be changed any more
+        cart.write_rom(0x4000, 0x3F);
+        cart.write_rom(0x6000, 0x00);
+        cart.write_rom(0x2000, 0x60);
+        assert_eq!(cart.read_rom(0x0000), 0x28);
+        assert_eq!(cart.read_rom(0x4000), 0x29);
+
+        cart.write_rom(0x0000, RAM_ENABLE_VALUE);
+        cart.write_ram(0xA000, 0x42);
+        assert_eq!(cart.ram[2 * RAM_BANK_SIZE], 0x42);
+        assert!(cart.save_changed());
+
+        // Locking doesn't survive a reset
+        cart.init();
+        assert_eq!(cart.read_rom(0x4000), 63);
+    }
+
+    #[test]
+    fn test_mode_lock() {
+        let mut cart = make_cart(8, 32 * 1024);
+        cart.write_rom(0x4000, MODE_LOCK_BIT);
+        cart.write_rom(0x0000, MAP_ENABLE_BIT | RAM_ENABLE_VALUE);
+
+        cart.write_rom(0x6000, 0x01);
+        cart.write_rom(0x4000, 0x03);
+        cart.write_ram(0xA000, 0x42);
+        assert_eq!(cart.ram[0], 0x42);
+    }
+}
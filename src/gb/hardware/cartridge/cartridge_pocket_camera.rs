@@ -0,0 +1,266 @@
+use crate::{
+    gb::{
+        MTime,
+        hardware::{
+            cartridge::{
+                Cartridge, CartridgeError, RAM_BANK_SIZE, ROM_BANK_0, ROM_BANK_N, RtcMode,
+                camera::{
+                    self, CameraImage, IMAGE_OFFSET, IMAGE_SIZE, REGISTER_COUNT, capture_started,
+                },
+                load_raw_ram,
+                rom::Rom,
+            },
+            memory::UNINIT_VALUE,
+        },
+        regions::{CART_RAM, MemoryRegion, ROM_SPACE},
+    },
+    region_guard,
+};
+use log::debug;
+
+const RAM_ENABLE: MemoryRegion = MemoryRegion::new(0x0000, 0x1FFF);
+const ROM_BANK: MemoryRegion = MemoryRegion::new(0x2000, 0x3FFF);
+const RAM_BANK: MemoryRegion = MemoryRegion::new(0x4000, 0x5FFF);
+
+const RAM_ENABLE_VALUE: u8 = 0x0A;
+const ROM_BANK_MASK: u8 = 0x3F;
+const RAM_BANK_MASK: u8 = 0x0F;
+// Any RAM bank with this bit set maps the sensor's registers instead
+const REGISTER_BANK_BIT: u8 = 0x10;
+// The registers repeat every $80 bytes
+const REGISTER_ADDRESS_MASK: u16 = 0x7F;
+// Only the low bits of the capture register are readable; the rest are write-only
+const CAPTURE_REGISTER_MASK: u8 = 0x07;
+
+pub const CAMERA_RAM_SIZE: usize = 128 * 1024;
+
+#[derive(Debug)]
+pub struct CartPocketCamera {
+    rom: Rom,
+    ram: Vec<u8>,
+    rom_banks: usize,
+    ram_dirty: bool,
+    image: CameraImage,
+    capture_cycles: u32,
+
+    // Registers
+    ram_enabled: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+    sensor: [u8; REGISTER_COUNT],
+}
+
+impl CartPocketCamera {
+    /// The camera always has 128 KiB of battery-backed RAM, whatever the header says.
+    pub fn new(rom_banks: usize) -> Self {
+        Self {
+            rom: Rom::default(),
+            ram: vec![UNINIT_VALUE; CAMERA_RAM_SIZE],
+            rom_banks,
+            ram_dirty: false,
+            image: CameraImage::default(),
+            capture_cycles: 0,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            sensor: [0; REGISTER_COUNT],
+        }
+    }
+
+    fn registers_selected(&self) -> bool {
+        self.ram_bank & REGISTER_BANK_BIT != 0
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        (self.ram_bank & RAM_BANK_MASK) as usize * RAM_BANK_SIZE
+            + CART_RAM.local_address(address) as usize
+    }
+
+    fn write_register(&mut self, address: u16, value: u8) {
+        let register = (CART_RAM.local_address(address) & REGISTER_ADDRESS_MASK) as usize;
+        if register >= REGISTER_COUNT {
+            return;
+        }
+
+        if register == 0 {
+            let was_capturing = capture_started(&self.sensor);
+            self.sensor[0] = value & CAPTURE_REGISTER_MASK;
+            if !capture_started(&self.sensor) {
+                self.capture_cycles = 0;
+            } else if !was_capturing {
+                self.capture_cycles = camera::capture_time(&self.sensor);
+                debug!("Camera capture started ({} M-cycles)", self.capture_cycles);
+            }
+        } else {
+            self.sensor[register] = value;
+        }
+    }
+
+    fn finish_capture(&mut self) {
+        camera::capture(
+            &self.sensor,
+            &self.image,
+            &mut self.ram[IMAGE_OFFSET..IMAGE_OFFSET + IMAGE_SIZE],
+        );
+        self.sensor[0] &= !0x01;
+        self.ram_dirty = true;
+    }
+}
+
+impl Cartridge for CartPocketCamera {
+    fn init(&mut self) {
+        self.ram_enabled = false;
+        self.rom_bank = 1;
+        self.ram_bank = 0;
+        self.sensor = [0; REGISTER_COUNT];
+        self.capture_cycles = 0;
+    }
+
+    fn step(&mut self, time: MTime) {
+        if self.capture_cycles == 0 {
+            return;
+        }
+        self.capture_cycles = self.capture_cycles.saturating_sub(time.0 as u32);
+        if self.capture_cycles == 0 {
+            self.finish_capture();
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        region_guard!(address in ROM_SPACE);
+
+        if ROM_BANK_0.contains(address) {
+            self.rom.read(ROM_BANK_0.local_address(address) as usize)
+        } else {
+            // Unlike the MBCs, bank 0 can be mapped here too
+            self.rom
+                .read_bank(self.rom_bank as usize, ROM_BANK_N.local_address(address))
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        region_guard!(address in ROM_SPACE);
+
+        if RAM_ENABLE.contains(address) {
+            self.ram_enabled = (value & 0x0F) == RAM_ENABLE_VALUE;
+        } else if ROM_BANK.contains(address) {
+            self.rom_bank = value & ROM_BANK_MASK;
+        } else if RAM_BANK.contains(address) {
+            self.ram_bank = value & (REGISTER_BANK_BIT | RAM_BANK_MASK);
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        region_guard!(address in CART_RAM);
+
+        // The RAM can be read even while it's disabled; only writes need enabling
+        if self.registers_selected() {
+            let register = CART_RAM.local_address(address) & REGISTER_ADDRESS_MASK;
+            if register == 0 { self.sensor[0] } else { 0x00 }
+        } else {
+            self.ram[self.ram_offset(address)]
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        region_guard!(address in CART_RAM);
+
+        if self.registers_selected() {
+            self.write_register(address, value);
+        } else if self.ram_enabled {
+            let offset = self.ram_offset(address);
+            self.ram[offset] = value;
+            self.ram_dirty = true;
+        }
+    }
+
+    fn set_camera_image(&mut self, image: CameraImage) {
+        self.image = image;
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn load_save_data(&mut self, data: &[u8], rtc_mode: RtcMode) {
+        load_raw_ram(&mut self.ram, data);
+    }
+
+    fn save_changed(&mut self) -> bool {
+        std::mem::take(&mut self.ram_dirty)
+    }
+
+    fn load_from_bytes(&mut self, rom: &[u8]) -> Result<(), CartridgeError> {
+        self.rom = Rom::new(rom, self.rom_banks);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gb::hardware::cartridge::{ROM_BANK_SIZE, bank_numbered_rom};
+    use test_log::test;
+
+    fn make_cart() -> CartPocketCamera {
+        let mut cart = CartPocketCamera::new(64);
+        cart.load_from_bytes(&bank_numbered_rom(ROM_BANK_SIZE, 64))
+            .unwrap();
+        cart.init();
+        cart
+    }
+
+    #[test]
+    fn test_banking() {
+        let mut cart = make_cart();
+        cart.write_rom(0x2000, 0x00);
+        assert_eq!(cart.read_rom(0x4000), 0x00);
+        cart.write_rom(0x2000, 0x3F);
+        assert_eq!(cart.read_rom(0x4000), 0x3F);
+
+        // Writes need RAM enabled, reads don't
+        cart.write_rom(0x4000, 0x0F);
+        cart.write_ram(0xA000, 0x42);
+        assert_eq!(cart.read_ram(0xA000), UNINIT_VALUE);
+        cart.write_rom(0x0000, RAM_ENABLE_VALUE);
+        cart.write_ram(0xA000, 0x42);
+        cart.write_rom(0x0000, 0x00);
+        assert_eq!(cart.read_ram(0xA000), 0x42);
+        assert_eq!(cart.ram[0x0F * RAM_BANK_SIZE], 0x42);
+    }
+
+    #[test]
+    fn test_capture() {
+        let mut cart = make_cart();
+        cart.write_rom(0x4000, REGISTER_BANK_BIT);
+
+        // Neutral exposure and a plain threshold at the middle for every pixel
+        cart.write_ram(0xA002, 0x10);
+        cart.write_ram(0xA003, 0x00);
+        for register in 0..48 {
+            cart.write_ram(0xA006 + register, 0x80);
+        }
+        // Only the capture register reads back, through any mirror
+        assert_eq!(cart.read_ram(0xA002), 0x00);
+
+        cart.write_ram(0xA080, 0x03);
+        assert_eq!(cart.read_ram(0xA000), 0x03);
+        cart.step(MTime(0x1000));
+        assert_eq!(cart.read_ram(0xA000) & 0x01, 0x01);
+        cart.step(MTime(0xFFFF));
+        cart.step(MTime(0xFFFF));
+        assert_eq!(cart.read_ram(0xA000), 0x02);
+        assert!(cart.save_changed());
+
+        // The test pattern's left edge is white and its right edge black
+        cart.write_rom(0x4000, 0x00);
+        let last_tile_row = IMAGE_OFFSET + 15 * 16;
+        assert_eq!(cart.read_ram(0xA000 + IMAGE_OFFSET as u16), 0x00);
+        assert_eq!(cart.read_ram(0xA000 + last_tile_row as u16), 0xFF);
+        assert_eq!(cart.read_ram(0xA001 + last_tile_row as u16), 0xFF);
+    }
+}
@@ -1,21 +1,35 @@
 use crate::{
-    error_panic,
     gb::{
-        hardware::{cartridge::Cartridge, memory::OPEN_BUS_VALUE},
+        hardware::{
+            cartridge::{
+                Cartridge, CartridgeError, RAM_BANK_SIZE, RtcMode, load_raw_ram, rom::Rom,
+            },
+            memory::{OPEN_BUS_VALUE, UNINIT_VALUE},
+        },
         regions::{CART_RAM, ROM_SPACE},
     },
-    region_guard, unwrap_or_log,
-};
-use std::{
-    fs::File,
-    io::{BufReader, Read},
+    region_guard,
 };
 
-const TOTAL_SIZE: usize = 0x8000;
+const TOTAL_BANKS: usize = 2;
 
 #[derive(Debug, Default)]
 pub struct CartRomOnly {
-    rom: Vec<u8>,
+    rom: Rom,
+    // Without an MBC there's no banking, so at most one bank of RAM wired straight to CART_RAM
+    ram: Vec<u8>,
+    has_battery: bool,
+    ram_dirty: bool,
+}
+
+impl CartRomOnly {
+    pub fn with_ram(has_battery: bool) -> Self {
+        Self {
+            ram: vec![UNINIT_VALUE; RAM_BANK_SIZE],
+            has_battery,
+            ..Default::default()
+        }
+    }
 }
 
 impl Cartridge for CartRomOnly {
@@ -26,7 +40,7 @@ impl Cartridge for CartRomOnly {
     fn read_rom(&self, address: u16) -> u8 {
         region_guard!(address in ROM_SPACE);
         // ROM_SPACE begins at 0 so no need to transform the address
-        self.rom[address as usize]
+        self.rom.read(address as usize)
     }
 
     fn write_rom(&mut self, address: u16, value: u8) {
@@ -36,48 +50,73 @@ impl Cartridge for CartRomOnly {
 
     fn read_ram(&self, address: u16) -> u8 {
         region_guard!(address in CART_RAM);
-        // No ram in this cart
-        OPEN_BUS_VALUE
+        // Nothing to enable first; the RAM (if any) is always there
+        match self.ram.get(CART_RAM.local_address(address) as usize) {
+            Some(value) => *value,
+            None => OPEN_BUS_VALUE,
+        }
     }
 
     fn write_ram(&mut self, address: u16, value: u8) {
         region_guard!(address in CART_RAM);
-        // Do nothing; ignore writes
+        if let Some(ram) = self.ram.get_mut(CART_RAM.local_address(address) as usize) {
+            *ram = value;
+            self.ram_dirty = true;
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.to_vec()
     }
 
-    fn load_from_file(&mut self, cart_file: &File) {
-        let mut reader = BufReader::new(cart_file);
+    fn load_save_data(&mut self, data: &[u8], rtc_mode: RtcMode) {
+        load_raw_ram(&mut self.ram, data);
+    }
 
-        // Fill the whole rom
-        self.rom.resize(TOTAL_SIZE, 0);
-        unwrap_or_log!(reader.read_exact(&mut self.rom));
+    fn save_changed(&mut self) -> bool {
+        std::mem::take(&mut self.ram_dirty)
+    }
 
-        // Ensure EOF
-        if unwrap_or_log!(reader.read(&mut self.rom)) != 0 {
-            error_panic!(
-                "Simple cartridges (no MBC, ROM only) should be exactly 32 KiB (32,768 bytes)"
-            );
-        };
+    fn load_from_bytes(&mut self, rom: &[u8]) -> Result<(), CartridgeError> {
+        // Simple cartridges (no MBC, ROM only) hold 32 KiB (32,768 bytes)
+        self.rom = Rom::new(rom, TOTAL_BANKS);
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::Path;
+    use std::fs;
     use test_log::test;
 
-    fn file(path: &str) -> File {
-        File::open(Path::new(path)).unwrap()
-    }
-
     #[test]
     fn test_load_cart() {
-        let f = file("res/dummy_cartromonly_read_test.bin");
+        let rom = fs::read("res/dummy_cartromonly_read_test.bin").unwrap();
         let mut cart = CartRomOnly::default();
-        cart.load_from_file(&f);
+        cart.load_from_bytes(&rom).unwrap();
         assert_eq!(cart.rom.len(), ROM_SPACE.size().into());
-        assert_eq!(cart.rom[ROM_SPACE.begin as usize], 0xAA);
-        assert_eq!(cart.rom[ROM_SPACE.end as usize], 0xBB);
+        assert_eq!(cart.read_rom(ROM_SPACE.begin), 0xAA);
+        assert_eq!(cart.read_rom(ROM_SPACE.end), 0xBB);
+    }
+
+    #[test]
+    fn test_ram() {
+        let mut cart = CartRomOnly::default();
+        cart.write_ram(0xA000, 0x12);
+        assert_eq!(cart.read_ram(0xA000), OPEN_BUS_VALUE);
+        assert!(!cart.save_changed());
+
+        let mut cart = CartRomOnly::with_ram(true);
+        cart.write_ram(0xA000, 0x12);
+        cart.write_ram(0xBFFF, 0x34);
+        assert_eq!(cart.read_ram(0xA000), 0x12);
+        assert_eq!(cart.read_ram(0xBFFF), 0x34);
+        assert!(cart.save_changed());
+        assert_eq!(cart.save_data().len(), RAM_BANK_SIZE);
     }
 }
@@ -0,0 +1,432 @@
+use crate::{
+    byte_fmt,
+    gb::{
+        MTime,
+        hardware::{
+            cartridge::{
+                Cartridge, CartridgeError, MTIME_PER_SECOND, ROM_BANK_0, ROM_BANK_N, RtcMode,
+                load_ram_and_footer, rom::Rom, unix_time,
+            },
+            memory::{OPEN_BUS_VALUE, UNINIT_VALUE},
+        },
+        regions::{CART_RAM, ROM_SPACE},
+    },
+    region_guard,
+};
+use log::{debug, info};
+
+// Only address bit 0 is decoded in CART_RAM: even addresses are the data port, odd ones select
+// which register it talks to. Every register is a single nibble.
+const REGISTER_SELECT_BIT: u16 = 0x0001;
+
+const REG_BANK_LOW: u8 = 0x0;
+const REG_BANK_HIGH: u8 = 0x1;
+const REG_WRITE_LOW: u8 = 0x4;
+const REG_WRITE_HIGH: u8 = 0x5;
+const REG_ADDRESS_HIGH: u8 = 0x6;
+const REG_ADDRESS_LOW: u8 = 0x7;
+const REG_ACTIVE: u8 = 0xA;
+const REG_READ_LOW: u8 = 0xC;
+const REG_READ_HIGH: u8 = 0xD;
+const WRITABLE_REGISTERS: usize = 8;
+
+// The unused upper nibble of every read is pulled high; the active register reads as ready
+const READ_FILL: u8 = 0xF0;
+const ACTIVE_VALUE: u8 = 0xF1;
+
+// The TAMA6 microcontroller's commands, from the upper bits of REG_ADDRESS_HIGH. Writing
+// REG_ADDRESS_LOW runs them.
+const CMD_RAM_WRITE: u8 = 0x0;
+const CMD_RAM_READ: u8 = 0x1;
+const CMD_CLOCK: u8 = 0x2;
+const CMD_CLOCK_READ: u8 = 0x4;
+
+// Clock commands, picked by the address
+const CLOCK_STOP: u8 = 0x0;
+const CLOCK_START: u8 = 0x1;
+const CLOCK_SET_MINUTES: u8 = 0x4;
+const CLOCK_SET_HOURS: u8 = 0x5;
+
+const ROM_BANK_MASK: u8 = 0x1F;
+const RAM_SIZE: usize = 0x20;
+const RAM_ADDRESS_MASK: u8 = 0x1F;
+
+// Appended to the save: a u64 UNIX timestamp, the seconds, minutes and hours, the day counter
+// as a u16, then whether the clock is running, all little endian
+const RTC_FOOTER_SIZE: usize = 14;
+
+/// The TAMA6's clock. Its time registers read back as BCD digits, one per nibble.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Tama6Clock {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days: u16,
+    running: bool,
+}
+
+impl Default for Tama6Clock {
+    fn default() -> Self {
+        Self {
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+            running: true,
+        }
+    }
+}
+
+impl Tama6Clock {
+    fn read_digit(&self, index: u8) -> u8 {
+        match index {
+            0x0 => self.seconds % 10,
+            0x1 => self.seconds / 10,
+            0x2 => self.minutes % 10,
+            0x3 => self.minutes / 10,
+            0x4 => self.hours % 10,
+            0x5 => self.hours / 10,
+            0x6 => (self.days % 7) as u8,
+            _ => 0,
+        }
+    }
+
+    fn advance_seconds(&mut self, seconds: u64) {
+        if !self.running {
+            return;
+        }
+        let total =
+            self.seconds as u64 + self.minutes as u64 * 60 + self.hours as u64 * 3600 + seconds;
+        self.seconds = (total % 60) as u8;
+        self.minutes = (total / 60 % 60) as u8;
+        self.hours = (total / 3600 % 24) as u8;
+        self.days = self.days.wrapping_add((total / 86_400) as u16);
+    }
+
+    fn write_footer(&self, footer: &mut Vec<u8>, timestamp: u64) {
+        footer.extend_from_slice(&timestamp.to_le_bytes());
+        footer.extend_from_slice(&[self.seconds, self.minutes, self.hours]);
+        footer.extend_from_slice(&self.days.to_le_bytes());
+        footer.push(self.running as u8);
+    }
+
+    fn read_footer(footer: &[u8]) -> (Self, u64) {
+        let clock = Self {
+            seconds: footer[8] % 60,
+            minutes: footer[9] % 60,
+            hours: footer[10] % 24,
+            days: u16::from_le_bytes([footer[11], footer[12]]),
+            running: footer[13] & 0x01 != 0,
+        };
+        let timestamp = u64::from_le_bytes(footer[..8].try_into().unwrap());
+        (clock, timestamp)
+    }
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0F)
+}
+
+/// Bandai's TAMA5, used only by Tamagotchi 3. Everything, including ROM banking, goes through
+/// a nibble-wide register file at $A000/$A001, which fronts a TAMA6 microcontroller holding 32
+/// bytes of battery-backed RAM and a clock.
+#[derive(Debug)]
+pub struct CartTama5 {
+    rom: Rom,
+    ram: Vec<u8>,
+    rom_banks: usize,
+    /// Set when RAM or the clock changes, so the save gets flushed.
+    save_dirty: bool,
+
+    // Registers
+    selected: u8,
+    registers: [u8; WRITABLE_REGISTERS],
+
+    // RTC
+    clock: Tama6Clock,
+    rtc_cycles: u32,
+}
+
+impl CartTama5 {
+    /// The TAMA5 always has its own RAM and a battery, whatever the header says.
+    pub fn new(rom_banks: usize) -> Self {
+        Self {
+            rom: Rom::default(),
+            ram: vec![UNINIT_VALUE; RAM_SIZE],
+            rom_banks,
+            save_dirty: false,
+            selected: 0,
+            registers: [0; WRITABLE_REGISTERS],
+            clock: Tama6Clock::default(),
+            rtc_cycles: 0,
+        }
+    }
+
+    fn rom_bank(&self) -> usize {
+        let bank =
+            (self.registers[REG_BANK_HIGH as usize] << 4) | self.registers[REG_BANK_LOW as usize];
+        (bank & ROM_BANK_MASK) as usize
+    }
+
+    fn command(&self) -> u8 {
+        self.registers[REG_ADDRESS_HIGH as usize] >> 1
+    }
+
+    fn command_address(&self) -> u8 {
+        ((self.registers[REG_ADDRESS_HIGH as usize] << 4)
+            | self.registers[REG_ADDRESS_LOW as usize])
+            & RAM_ADDRESS_MASK
+    }
+
+    fn write_value(&self) -> u8 {
+        (self.registers[REG_WRITE_HIGH as usize] << 4) | self.registers[REG_WRITE_LOW as usize]
+    }
+
+    /// The byte the pending read command produces, split over REG_READ_LOW/REG_READ_HIGH.
+    fn read_value(&self) -> u8 {
+        match self.command() {
+            CMD_RAM_READ => self.ram[self.command_address() as usize],
+            CMD_CLOCK_READ => self
+                .clock
+                .read_digit(self.registers[REG_ADDRESS_LOW as usize]),
+            _ => 0,
+        }
+    }
+
+    fn run_command(&mut self) {
+        let address = self.command_address();
+        let value = self.write_value();
+        match self.command() {
+            CMD_RAM_WRITE => {
+                self.ram[address as usize] = value;
+                self.save_dirty = true;
+            }
+            CMD_CLOCK => {
+                self.save_dirty = true;
+                match address {
+                    CLOCK_STOP => self.clock.running = false,
+                    CLOCK_START => self.clock.running = true,
+                    CLOCK_SET_MINUTES => {
+                        self.clock.minutes = from_bcd(value) % 60;
+                        self.clock.seconds = 0;
+                        self.rtc_cycles = 0;
+                    }
+                    CLOCK_SET_HOURS => self.clock.hours = from_bcd(value) % 24,
+                    _ => debug!(
+                        "Ignoring unknown TAMA6 clock command {}",
+                        byte_fmt!(address)
+                    ),
+                }
+            }
+            // Reads are answered through REG_READ_LOW/REG_READ_HIGH
+            CMD_RAM_READ | CMD_CLOCK_READ => (),
+            command => debug!("Ignoring unknown TAMA6 command {}", byte_fmt!(command)),
+        }
+    }
+}
+
+impl Cartridge for CartTama5 {
+    fn init(&mut self) {
+        self.selected = 0;
+        self.registers = [0; WRITABLE_REGISTERS];
+    }
+
+    fn step(&mut self, time: MTime) {
+        self.rtc_cycles += time.0 as u32;
+        while self.rtc_cycles >= MTIME_PER_SECOND {
+            self.rtc_cycles -= MTIME_PER_SECOND;
+            self.clock.advance_seconds(1);
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        region_guard!(address in ROM_SPACE);
+
+        if ROM_BANK_0.contains(address) {
+            self.rom.read(ROM_BANK_0.local_address(address) as usize)
+        } else {
+            self.rom
+                .read_bank(self.rom_bank(), ROM_BANK_N.local_address(address))
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        region_guard!(address in ROM_SPACE);
+        // There are no registers in ROM space; everything goes through CART_RAM
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        region_guard!(address in CART_RAM);
+
+        if address & REGISTER_SELECT_BIT != 0 {
+            return OPEN_BUS_VALUE;
+        }
+        match self.selected {
+            REG_ACTIVE => ACTIVE_VALUE,
+            REG_READ_LOW => READ_FILL | (self.read_value() & 0x0F),
+            REG_READ_HIGH => READ_FILL | (self.read_value() >> 4),
+            _ => OPEN_BUS_VALUE,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        region_guard!(address in CART_RAM);
+
+        let value = value & 0x0F;
+        if address & REGISTER_SELECT_BIT != 0 {
+            self.selected = value;
+        } else if let Some(register) = self.registers.get_mut(self.selected as usize) {
+            *register = value;
+            if self.selected == REG_ADDRESS_LOW {
+                self.run_command();
+            }
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        let mut data = self.ram.to_vec();
+        self.clock.write_footer(&mut data, unix_time());
+        data
+    }
+
+    fn load_save_data(&mut self, data: &[u8], rtc_mode: RtcMode) {
+        let Some(footer) = load_ram_and_footer(&mut self.ram, data, &[RTC_FOOTER_SIZE]) else {
+            return;
+        };
+
+        let (clock, timestamp) = Tama6Clock::read_footer(footer);
+        self.clock = clock;
+
+        if rtc_mode == RtcMode::CatchUp {
+            let elapsed = unix_time().saturating_sub(timestamp);
+            info!("Advancing the RTC by {elapsed} seconds since the last save.");
+            self.clock.advance_seconds(elapsed);
+        }
+    }
+
+    fn save_changed(&mut self) -> bool {
+        std::mem::take(&mut self.save_dirty)
+    }
+
+    fn load_from_bytes(&mut self, rom: &[u8]) -> Result<(), CartridgeError> {
+        self.rom = Rom::new(rom, self.rom_banks);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gb::hardware::cartridge::{ROM_BANK_SIZE, bank_numbered_rom};
+    use test_log::test;
+
+    fn make_cart() -> CartTama5 {
+        let mut cart = CartTama5::new(32);
+        cart.load_from_bytes(&bank_numbered_rom(ROM_BANK_SIZE, 32))
+            .unwrap();
+        cart.init();
+        cart
+    }
+
+    fn write_register(cart: &mut CartTama5, register: u8, value: u8) {
+        cart.write_ram(0xA001, register);
+        cart.write_ram(0xA000, value);
+    }
+
+    fn read_register(cart: &mut CartTama5, register: u8) -> u8 {
+        cart.write_ram(0xA001, register);
+        cart.read_ram(0xA000)
+    }
+
+    /// Sends a TAMA6 command the way the game does: value, then the address, which runs it.
+    fn run_command(cart: &mut CartTama5, command: u8, address: u8, value: u8) {
+        write_register(cart, REG_WRITE_LOW, value & 0x0F);
+        write_register(cart, REG_WRITE_HIGH, value >> 4);
+        write_register(cart, REG_ADDRESS_HIGH, (command << 1) | (address >> 4));
+        write_register(cart, REG_ADDRESS_LOW, address & 0x0F);
+    }
+
+    fn read_byte(cart: &mut CartTama5) -> u8 {
+        let low = read_register(cart, REG_READ_LOW) & 0x0F;
+        let high = read_register(cart, REG_READ_HIGH) & 0x0F;
+        (high << 4) | low
+    }
+
+    #[test]
+    fn test_banking() {
+        let mut cart = make_cart();
+        assert_eq!(read_register(&mut cart, REG_ACTIVE), ACTIVE_VALUE);
+
+        write_register(&mut cart, REG_BANK_LOW, 0x3);
+        write_register(&mut cart, REG_BANK_HIGH, 0x1);
+        assert_eq!(cart.read_rom(0x4000), 0x13);
+        write_register(&mut cart, REG_BANK_HIGH, 0x0);
+        assert_eq!(cart.read_rom(0x4000), 0x03);
+
+        // Registers are nibbles; the upper bits of a write are dropped
+        write_register(&mut cart, REG_BANK_LOW, 0xF5);
+        assert_eq!(cart.read_rom(0x4000), 0x05);
+    }
+
+    #[test]
+    fn test_ram() {
+        let mut cart = make_cart();
+        run_command(&mut cart, CMD_RAM_WRITE, 0x1F, 0xA5);
+        run_command(&mut cart, CMD_RAM_WRITE, 0x00, 0x3C);
+        assert!(cart.save_changed());
+
+        run_command(&mut cart, CMD_RAM_READ, 0x1F, 0);
+        assert_eq!(read_byte(&mut cart), 0xA5);
+        run_command(&mut cart, CMD_RAM_READ, 0x00, 0);
+        assert_eq!(read_byte(&mut cart), 0x3C);
+        assert_eq!(read_register(&mut cart, REG_READ_HIGH), READ_FILL | 0x3);
+    }
+
+    #[test]
+    fn test_clock() {
+        let mut cart = make_cart();
+        run_command(&mut cart, CMD_CLOCK, CLOCK_SET_HOURS, 0x23);
+        run_command(&mut cart, CMD_CLOCK, CLOCK_SET_MINUTES, 0x59);
+        assert!(cart.save_changed());
+        cart.step(MTime(0xFFFF));
+        for _ in 0..(MTIME_PER_SECOND * 61 / 0xFFFF) {
+            cart.step(MTime(0xFFFF));
+        }
+
+        // 00:00:01 the next day
+        let digits: Vec<u8> = (0..7)
+            .map(|digit| {
+                run_command(&mut cart, CMD_CLOCK_READ, digit, 0);
+                read_byte(&mut cart)
+            })
+            .collect();
+        assert_eq!(digits, [1, 0, 0, 0, 0, 0, 1]);
+
+        // A stopped clock doesn't count
+        run_command(&mut cart, CMD_CLOCK, CLOCK_STOP, 0);
+        cart.clock.advance_seconds(3600);
+        assert_eq!(cart.clock.hours, 0);
+    }
+
+    #[test]
+    fn test_save_footer() {
+        let mut cart = make_cart();
+        cart.clock = Tama6Clock {
+            seconds: 30,
+            minutes: 59,
+            hours: 23,
+            days: 5,
+            running: true,
+        };
+        let data = cart.save_data();
+        assert_eq!(data.len(), RAM_SIZE + RTC_FOOTER_SIZE);
+
+        let mut loaded = make_cart();
+        loaded.load_save_data(&data, RtcMode::Frozen);
+        assert_eq!(loaded.clock, cart.clock);
+    }
+}
@@ -0,0 +1,181 @@
+use crate::byte_fmt;
+use log::debug;
+
+const ERASED_VALUE: u8 = 0xFF;
+
+// Commands only take effect after this unlock sequence
+const UNLOCK_ADDRESS_1: usize = 0x5555;
+const UNLOCK_VALUE_1: u8 = 0xAA;
+const UNLOCK_ADDRESS_2: usize = 0x2AAA;
+const UNLOCK_VALUE_2: u8 = 0x55;
+
+const CMD_PROGRAM: u8 = 0xA0;
+const CMD_ERASE: u8 = 0x80;
+const CMD_ID: u8 = 0x90;
+const CMD_RESET: u8 = 0xF0;
+const ERASE_CHIP: u8 = 0x10;
+const ERASE_SECTOR: u8 = 0x30;
+
+// Macronix MX29F008
+const MANUFACTURER_ID: u8 = 0xC2;
+const DEVICE_ID: u8 = 0x81;
+
+pub const FLASH_SIZE: usize = 1024 * 1024;
+const SECTOR_SIZE: usize = 128 * 1024;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum FlashState {
+    #[default]
+    Read,
+    Unlock1,
+    Unlock2,
+    Program,
+    EraseSetup,
+    EraseUnlock1,
+    EraseUnlock2,
+    Id,
+}
+
+/// An AMD-style flash chip. Programming and erasing finish instantly, so the status polling
+/// games do always sees the operation done.
+#[derive(Debug)]
+pub struct Flash {
+    data: Vec<u8>,
+    state: FlashState,
+}
+
+impl Flash {
+    pub fn new(size: usize) -> Self {
+        Self {
+            data: vec![ERASED_VALUE; size],
+            state: FlashState::Read,
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    pub fn read(&self, offset: usize) -> u8 {
+        if self.state == FlashState::Id {
+            match offset & 0x01 {
+                0 => MANUFACTURER_ID,
+                _ => DEVICE_ID,
+            }
+        } else {
+            self.data[offset % self.data.len()]
+        }
+    }
+
+    /// Feeds a write into the command state machine. Returns true if the contents changed.
+    pub fn write(&mut self, offset: usize, value: u8) -> bool {
+        let offset = offset % self.data.len();
+        let unlock_1 = offset == UNLOCK_ADDRESS_1 && value == UNLOCK_VALUE_1;
+        let unlock_2 = offset == UNLOCK_ADDRESS_2 && value == UNLOCK_VALUE_2;
+        let mut changed = false;
+
+        self.state = match self.state {
+            _ if value == CMD_RESET && self.state != FlashState::Program => FlashState::Read,
+            FlashState::Read | FlashState::Id if unlock_1 => FlashState::Unlock1,
+            FlashState::Unlock1 if unlock_2 => FlashState::Unlock2,
+            FlashState::Unlock2 if offset == UNLOCK_ADDRESS_1 => match value {
+                CMD_PROGRAM => FlashState::Program,
+                CMD_ERASE => FlashState::EraseSetup,
+                CMD_ID => FlashState::Id,
+                _ => {
+                    debug!("Ignoring unknown flash command {}", byte_fmt!(value));
+                    FlashState::Read
+                }
+            },
+            FlashState::Program => {
+                // Programming can only clear bits; setting them again takes an erase
+                self.data[offset] &= value;
+                changed = true;
+                FlashState::Read
+            }
+            FlashState::EraseSetup if unlock_1 => FlashState::EraseUnlock1,
+            FlashState::EraseUnlock1 if unlock_2 => FlashState::EraseUnlock2,
+            FlashState::EraseUnlock2 if value == ERASE_CHIP && offset == UNLOCK_ADDRESS_1 => {
+                self.data.fill(ERASED_VALUE);
+                changed = true;
+                FlashState::Read
+            }
+            FlashState::EraseUnlock2 if value == ERASE_SECTOR => {
+                let start = offset - offset % SECTOR_SIZE;
+                let end = (start + SECTOR_SIZE).min(self.data.len());
+                self.data[start..end].fill(ERASED_VALUE);
+                changed = true;
+                FlashState::Read
+            }
+            FlashState::Id => FlashState::Id,
+            _ => FlashState::Read,
+        };
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    fn unlock(flash: &mut Flash, command: u8) {
+        flash.write(UNLOCK_ADDRESS_1, UNLOCK_VALUE_1);
+        flash.write(UNLOCK_ADDRESS_2, UNLOCK_VALUE_2);
+        flash.write(UNLOCK_ADDRESS_1, command);
+    }
+
+    #[test]
+    fn test_program() {
+        let mut flash = Flash::new(FLASH_SIZE);
+
+        // Plain writes don't change anything
+        assert!(!flash.write(0x1234, 0x00));
+        assert_eq!(flash.read(0x1234), ERASED_VALUE);
+
+        unlock(&mut flash, CMD_PROGRAM);
+        assert!(flash.write(0x1234, 0x5A));
+        assert_eq!(flash.read(0x1234), 0x5A);
+
+        // Programming again only clears bits
+        unlock(&mut flash, CMD_PROGRAM);
+        flash.write(0x1234, 0xF0);
+        assert_eq!(flash.read(0x1234), 0x50);
+    }
+
+    #[test]
+    fn test_erase() {
+        let mut flash = Flash::new(FLASH_SIZE);
+        for offset in [0x1234, SECTOR_SIZE + 0x10] {
+            unlock(&mut flash, CMD_PROGRAM);
+            flash.write(offset, 0x00);
+        }
+
+        unlock(&mut flash, CMD_ERASE);
+        flash.write(UNLOCK_ADDRESS_1, UNLOCK_VALUE_1);
+        flash.write(UNLOCK_ADDRESS_2, UNLOCK_VALUE_2);
+        assert!(flash.write(SECTOR_SIZE + 0x1000, ERASE_SECTOR));
+        assert_eq!(flash.read(0x1234), 0x00);
+        assert_eq!(flash.read(SECTOR_SIZE + 0x10), ERASED_VALUE);
+
+        unlock(&mut flash, CMD_ERASE);
+        flash.write(UNLOCK_ADDRESS_1, UNLOCK_VALUE_1);
+        flash.write(UNLOCK_ADDRESS_2, UNLOCK_VALUE_2);
+        flash.write(UNLOCK_ADDRESS_1, ERASE_CHIP);
+        assert_eq!(flash.read(0x1234), ERASED_VALUE);
+    }
+
+    #[test]
+    fn test_id() {
+        let mut flash = Flash::new(FLASH_SIZE);
+        unlock(&mut flash, CMD_ID);
+        assert_eq!(flash.read(0), MANUFACTURER_ID);
+        assert_eq!(flash.read(1), DEVICE_ID);
+        flash.write(0, CMD_RESET);
+        assert_eq!(flash.read(0), ERASED_VALUE);
+    }
+}
@@ -0,0 +1,638 @@
+use crate::{
+    byte_fmt,
+    gb::hardware::cartridge::{
+        HEADER_CART_TYPE, HEADER_CHECKSUM, HEADER_GLOBAL_CHECKSUM, HEADER_LOGO, HEADER_RAM_SIZE,
+        HEADER_ROM_SIZE, HEADER_TITLE, NINTENDO_LOGO, ROM_BANK_SIZE, try_decode_ram_size,
+        try_decode_rom_banks,
+    },
+    gb::regions::{HEADER, MemoryRegion},
+};
+use std::fmt::Display;
+
+pub const HEADER_MANUFACTURER: MemoryRegion = MemoryRegion::new(0x013F, 0x0142);
+pub const HEADER_CGB_FLAG: u16 = 0x0143;
+pub const HEADER_NEW_LICENSEE: MemoryRegion = MemoryRegion::new(0x0144, 0x0145);
+pub const HEADER_SGB_FLAG: u16 = 0x0146;
+pub const HEADER_DESTINATION: u16 = 0x014A;
+pub const HEADER_OLD_LICENSEE: u16 = 0x014B;
+pub const HEADER_VERSION: u16 = 0x014C;
+
+// The header checksum covers everything from the title up to the version number
+const CHECKSUMMED: MemoryRegion = MemoryRegion::new(HEADER_TITLE.begin, HEADER_VERSION);
+
+// Old licensee code meaning "look at the new licensee code instead"
+pub const USE_NEW_LICENSEE: u8 = 0x33;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CgbSupport {
+    /// Made for the original Game Boy only.
+    #[default]
+    None,
+    /// Enhanced for the Game Boy Color, but still works on older models.
+    Compatible,
+    /// Only works on the Game Boy Color.
+    Only,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    #[default]
+    Japan,
+    Overseas,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderWarning {
+    /// The file is too small to even contain a header.
+    TooShort {
+        len: usize,
+    },
+    /// The logo doesn't match the one the boot ROM checks for.
+    BadLogo,
+    HeaderChecksumMismatch {
+        stored: u8,
+        computed: u8,
+    },
+    GlobalChecksumMismatch {
+        stored: u16,
+        computed: u16,
+    },
+    UnknownCartType(u8),
+    UnknownRomSize(u8),
+    UnknownRamSize(u8),
+    /// The file isn't the size the header says the ROM is.
+    RomSizeMismatch {
+        header: usize,
+        file: usize,
+    },
+}
+
+impl Display for HeaderWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeaderWarning::TooShort { len } => {
+                write!(
+                    f,
+                    "File is only {len} bytes, which is too short to have a header"
+                )
+            }
+            HeaderWarning::BadLogo => write!(f, "Logo doesn't match the Nintendo logo"),
+            HeaderWarning::HeaderChecksumMismatch { stored, computed } => write!(
+                f,
+                "Header checksum is {} but should be {}",
+                byte_fmt!(stored),
+                byte_fmt!(computed)
+            ),
+            HeaderWarning::GlobalChecksumMismatch { stored, computed } => write!(
+                f,
+                "Global checksum is ${stored:0>4X} but should be ${computed:0>4X}"
+            ),
+            HeaderWarning::UnknownCartType(code) => {
+                write!(f, "Unknown cart type {}", byte_fmt!(code))
+            }
+            HeaderWarning::UnknownRomSize(code) => {
+                write!(f, "Unknown ROM size {}", byte_fmt!(code))
+            }
+            HeaderWarning::UnknownRamSize(code) => {
+                write!(f, "Unknown RAM size {}", byte_fmt!(code))
+            }
+            HeaderWarning::RomSizeMismatch { header, file } => write!(
+                f,
+                "Header says the ROM is {header} bytes, but the file is {file} bytes"
+            ),
+        }
+    }
+}
+
+/// Every field of the cartridge header, decoded, plus anything that looked wrong about it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CartridgeHeader {
+    pub title: String,
+    pub manufacturer_code: Option<String>,
+    pub cgb_support: CgbSupport,
+    pub new_licensee_code: String,
+    pub sgb_support: bool,
+    pub cart_type: u8,
+    pub rom_size_code: u8,
+    pub ram_size_code: u8,
+    /// Decoded from `rom_size_code`; `None` if the code is unknown.
+    pub rom_banks: Option<usize>,
+    /// Decoded from `ram_size_code`; `None` if the code is unknown.
+    pub ram_size: Option<usize>,
+    pub destination: Destination,
+    pub old_licensee_code: u8,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+
+    pub logo_valid: bool,
+    pub header_checksum_valid: bool,
+    pub global_checksum_valid: bool,
+    pub warnings: Vec<HeaderWarning>,
+}
+
+impl CartridgeHeader {
+    /// Parses the header out of a whole ROM image. This never fails; anything that's wrong is
+    /// reported in `warnings` instead.
+    pub fn parse(rom: &[u8]) -> Self {
+        let mut header = CartridgeHeader::default();
+        if rom.len() <= HEADER.end as usize {
+            header
+                .warnings
+                .push(HeaderWarning::TooShort { len: rom.len() });
+            return header;
+        }
+
+        let byte = |address: u16| rom[address as usize];
+        let bytes = |region: MemoryRegion| &rom[region.begin as usize..=region.end as usize];
+
+        // On CGB carts, the end of the title area was repurposed
+        let cgb_flag = byte(HEADER_CGB_FLAG);
+        header.cgb_support = match cgb_flag {
+            0xC0 => CgbSupport::Only,
+            flag if flag & 0x80 != 0 => CgbSupport::Compatible,
+            _ => CgbSupport::None,
+        };
+        let title_end = if header.cgb_support == CgbSupport::None {
+            HEADER_TITLE.end
+        } else {
+            let manufacturer = bytes(HEADER_MANUFACTURER);
+            if manufacturer.iter().all(|c| c.is_ascii_uppercase()) {
+                header.manufacturer_code = Some(to_text(manufacturer));
+                HEADER_MANUFACTURER.begin - 1
+            } else {
+                HEADER_CGB_FLAG - 1
+            }
+        };
+        header.title = to_text(bytes(MemoryRegion::new(HEADER_TITLE.begin, title_end)));
+
+        header.new_licensee_code = to_text(bytes(HEADER_NEW_LICENSEE));
+        header.sgb_support = byte(HEADER_SGB_FLAG) == 0x03;
+        header.cart_type = byte(HEADER_CART_TYPE);
+        header.rom_size_code = byte(HEADER_ROM_SIZE);
+        header.ram_size_code = byte(HEADER_RAM_SIZE);
+        header.rom_banks = try_decode_rom_banks(header.rom_size_code);
+        header.ram_size = try_decode_ram_size(header.ram_size_code);
+        header.destination = match byte(HEADER_DESTINATION) {
+            0x00 => Destination::Japan,
+            _ => Destination::Overseas,
+        };
+        header.old_licensee_code = byte(HEADER_OLD_LICENSEE);
+        header.version = byte(HEADER_VERSION);
+        header.header_checksum = byte(HEADER_CHECKSUM);
+        header.global_checksum =
+            u16::from_be_bytes(bytes(HEADER_GLOBAL_CHECKSUM).try_into().unwrap());
+
+        // Validation
+        header.logo_valid = bytes(HEADER_LOGO) == NINTENDO_LOGO;
+        if !header.logo_valid {
+            header.warnings.push(HeaderWarning::BadLogo);
+        }
+
+        let computed = compute_header_checksum(rom);
+        header.header_checksum_valid = computed == header.header_checksum;
+        if !header.header_checksum_valid {
+            header.warnings.push(HeaderWarning::HeaderChecksumMismatch {
+                stored: header.header_checksum,
+                computed,
+            });
+        }
+
+        let computed = compute_global_checksum(rom);
+        header.global_checksum_valid = computed == header.global_checksum;
+        if !header.global_checksum_valid {
+            header.warnings.push(HeaderWarning::GlobalChecksumMismatch {
+                stored: header.global_checksum,
+                computed,
+            });
+        }
+
+        if cart_type_name(header.cart_type).is_none() {
+            header
+                .warnings
+                .push(HeaderWarning::UnknownCartType(header.cart_type));
+        }
+        match header.rom_banks {
+            None => header
+                .warnings
+                .push(HeaderWarning::UnknownRomSize(header.rom_size_code)),
+            Some(banks) if banks * ROM_BANK_SIZE != rom.len() => {
+                header.warnings.push(HeaderWarning::RomSizeMismatch {
+                    header: banks * ROM_BANK_SIZE,
+                    file: rom.len(),
+                })
+            }
+            Some(_) => (),
+        }
+        if header.ram_size.is_none() {
+            header
+                .warnings
+                .push(HeaderWarning::UnknownRamSize(header.ram_size_code));
+        }
+
+        header
+    }
+
+    /// Whether this cart uses the new (two character) licensee code.
+    pub fn uses_new_licensee(&self) -> bool {
+        self.old_licensee_code == USE_NEW_LICENSEE
+    }
+
+    pub fn licensee_name(&self) -> Option<&'static str> {
+        if self.uses_new_licensee() {
+            new_licensee_name(&self.new_licensee_code)
+        } else {
+            old_licensee_name(self.old_licensee_code)
+        }
+    }
+
+    pub fn cart_type_name(&self) -> Option<&'static str> {
+        cart_type_name(self.cart_type)
+    }
+}
+
+/// The same checksum the boot ROM verifies; the cart won't boot on real hardware without it.
+pub fn compute_header_checksum(rom: &[u8]) -> u8 {
+    rom[CHECKSUMMED.begin as usize..=CHECKSUMMED.end as usize]
+        .iter()
+        .fold(0u8, |sum, b| sum.wrapping_sub(*b).wrapping_sub(1))
+}
+
+/// Sum of every byte in the ROM except the global checksum itself. Nothing actually checks this.
+pub fn compute_global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|(i, _)| {
+            !(HEADER_GLOBAL_CHECKSUM.begin as usize..=HEADER_GLOBAL_CHECKSUM.end as usize)
+                .contains(i)
+        })
+        .fold(0u16, |sum, (_, b)| sum.wrapping_add(*b as u16))
+}
+
+pub fn cart_type_name(cart_type: u8) -> Option<&'static str> {
+    Some(match cart_type {
+        0x00 => "ROM ONLY",
+        0x01 => "MBC1",
+        0x02 => "MBC1+RAM",
+        0x03 => "MBC1+RAM+BATTERY",
+        0x05 => "MBC2",
+        0x06 => "MBC2+BATTERY",
+        0x08 => "ROM+RAM",
+        0x09 => "ROM+RAM+BATTERY",
+        0x0B => "MMM01",
+        0x0C => "MMM01+RAM",
+        0x0D => "MMM01+RAM+BATTERY",
+        0x0F => "MBC3+TIMER+BATTERY",
+        0x10 => "MBC3+TIMER+RAM+BATTERY",
+        0x11 => "MBC3",
+        0x12 => "MBC3+RAM",
+        0x13 => "MBC3+RAM+BATTERY",
+        0x19 => "MBC5",
+        0x1A => "MBC5+RAM",
+        0x1B => "MBC5+RAM+BATTERY",
+        0x1C => "MBC5+RUMBLE",
+        0x1D => "MBC5+RUMBLE+RAM",
+        0x1E => "MBC5+RUMBLE+RAM+BATTERY",
+        0x20 => "MBC6",
+        0x22 => "MBC7+SENSOR+RUMBLE+RAM+BATTERY",
+        0xFC => "POCKET CAMERA",
+        0xFD => "BANDAI TAMA5",
+        0xFE => "HuC3",
+        0xFF => "HuC1+RAM+BATTERY",
+        _ => return None,
+    })
+}
+
+pub fn old_licensee_name(code: u8) -> Option<&'static str> {
+    Some(match code {
+        0x00 => "None",
+        0x01 => "Nintendo",
+        0x08 => "Capcom",
+        0x09 => "HOT-B",
+        0x0A => "Jaleco",
+        0x0B => "Coconuts Japan",
+        0x0C => "Elite Systems",
+        0x13 => "EA (Electronic Arts)",
+        0x18 => "Hudson Soft",
+        0x19 => "ITC Entertainment",
+        0x1A => "Yanoman",
+        0x1D => "Japan Clary",
+        0x1F => "Virgin Games Ltd.",
+        0x24 => "PCM Complete",
+        0x25 => "San-X",
+        0x28 => "Kemco",
+        0x29 => "SETA Corporation",
+        0x30 => "Infogrames",
+        0x31 => "Nintendo",
+        0x32 => "Bandai",
+        0x34 => "Konami",
+        0x35 => "HectorSoft",
+        0x38 => "Capcom",
+        0x39 => "Banpresto",
+        0x3C => "Entertainment Interactive",
+        0x3E => "Gremlin",
+        0x41 => "Ubi Soft",
+        0x42 => "Atlus",
+        0x44 => "Malibu Interactive",
+        0x46 => "Angel",
+        0x47 => "Spectrum HoloByte",
+        0x49 => "Irem",
+        0x4A => "Virgin Games Ltd.",
+        0x4D => "Malibu Interactive",
+        0x4F => "U.S. Gold",
+        0x50 => "Absolute",
+        0x51 => "Acclaim Entertainment",
+        0x52 => "Activision",
+        0x53 => "Sammy USA Corporation",
+        0x54 => "GameTek",
+        0x55 => "Park Place",
+        0x56 => "LJN",
+        0x57 => "Matchbox",
+        0x59 => "Milton Bradley Company",
+        0x5A => "Mindscape",
+        0x5B => "Romstar",
+        0x5C => "Naxat Soft",
+        0x5D => "Tradewest",
+        0x60 => "Titus Interactive",
+        0x61 => "Virgin Games Ltd.",
+        0x67 => "Ocean Software",
+        0x69 => "EA (Electronic Arts)",
+        0x6E => "Elite Systems",
+        0x6F => "Electro Brain",
+        0x70 => "Infogrames",
+        0x71 => "Interplay Entertainment",
+        0x72 => "Broderbund",
+        0x73 => "Sculptured Software",
+        0x75 => "The Sales Curve Limited",
+        0x78 => "THQ",
+        0x79 => "Accolade",
+        0x7A => "Triffix Entertainment",
+        0x7C => "MicroProse",
+        0x7F => "Kemco",
+        0x80 => "Misawa Entertainment",
+        0x83 => "LOZC G.",
+        0x86 => "Tokuma Shoten",
+        0x8B => "Bullet-Proof Software",
+        0x8C => "Vic Tokai Corp.",
+        0x8E => "Ape Inc.",
+        0x8F => "I'Max",
+        0x91 => "Chunsoft Co.",
+        0x92 => "Video System",
+        0x93 => "Tsubaraya Productions",
+        0x95 => "Varie",
+        0x96 => "Yonezawa/S'Pal",
+        0x97 => "Kemco",
+        0x99 => "Arc",
+        0x9A => "Nihon Bussan",
+        0x9B => "Tecmo",
+        0x9C => "Imagineer",
+        0x9D => "Banpresto",
+        0x9F => "Nova",
+        0xA1 => "Hori Electric",
+        0xA2 => "Bandai",
+        0xA4 => "Konami",
+        0xA6 => "Kawada",
+        0xA7 => "Takara",
+        0xA9 => "Technos Japan",
+        0xAA => "Broderbund",
+        0xAC => "Toei Animation",
+        0xAD => "Toho",
+        0xAF => "Namco",
+        0xB0 => "Acclaim Entertainment",
+        0xB1 => "ASCII Corporation or Nexsoft",
+        0xB2 => "Bandai",
+        0xB4 => "Square Enix",
+        0xB6 => "HAL Laboratory",
+        0xB7 => "SNK",
+        0xB9 => "Pony Canyon",
+        0xBA => "Culture Brain",
+        0xBB => "Sunsoft",
+        0xBD => "Sony Imagesoft",
+        0xBF => "Sammy Corporation",
+        0xC0 => "Taito",
+        0xC2 => "Kemco",
+        0xC3 => "Square",
+        0xC4 => "Tokuma Shoten",
+        0xC5 => "Data East",
+        0xC6 => "Tonkin House",
+        0xC8 => "Koei",
+        0xC9 => "UFL",
+        0xCA => "Ultra Games",
+        0xCB => "VAP, Inc.",
+        0xCC => "Use Corporation",
+        0xCD => "Meldac",
+        0xCE => "Pony Canyon",
+        0xCF => "Angel",
+        0xD0 => "Taito",
+        0xD1 => "SOFEL",
+        0xD2 => "Quest",
+        0xD3 => "Sigma Enterprises",
+        0xD4 => "ASK Kodansha Co.",
+        0xD6 => "Naxat Soft",
+        0xD7 => "Copya System",
+        0xD9 => "Banpresto",
+        0xDA => "Tomy",
+        0xDB => "LJN",
+        0xDD => "Nippon Computer Systems",
+        0xDE => "Human Ent.",
+        0xDF => "Altron",
+        0xE0 => "Jaleco",
+        0xE1 => "Towa Chiki",
+        0xE2 => "Yutaka",
+        0xE3 => "Varie",
+        0xE5 => "Epoch",
+        0xE7 => "Athena",
+        0xE8 => "Asmik Ace Entertainment",
+        0xE9 => "Natsume",
+        0xEA => "King Records",
+        0xEB => "Atlus",
+        0xEC => "Epic/Sony Records",
+        0xEE => "IGS",
+        0xF0 => "A Wave",
+        0xF3 => "Extreme Entertainment",
+        0xFF => "LJN",
+        _ => return None,
+    })
+}
+
+pub fn new_licensee_name(code: &str) -> Option<&'static str> {
+    Some(match code {
+        "00" => "None",
+        "01" => "Nintendo Research & Development 1",
+        "08" => "Capcom",
+        "13" => "EA (Electronic Arts)",
+        "18" => "Hudson Soft",
+        "19" => "B-AI",
+        "20" => "KSS",
+        "22" => "Planning Office WADA",
+        "24" => "PCM Complete",
+        "25" => "San-X",
+        "28" => "Kemco",
+        "29" => "SETA Corporation",
+        "30" => "Viacom",
+        "31" => "Nintendo",
+        "32" => "Bandai",
+        "33" => "Ocean Software/Acclaim Entertainment",
+        "34" => "Konami",
+        "35" => "HectorSoft",
+        "37" => "Taito",
+        "38" => "Hudson Soft",
+        "39" => "Banpresto",
+        "41" => "Ubi Soft",
+        "42" => "Atlus",
+        "44" => "Malibu Interactive",
+        "46" => "Angel",
+        "47" => "Bullet-Proof Software",
+        "49" => "Irem",
+        "50" => "Absolute",
+        "51" => "Acclaim Entertainment",
+        "52" => "Activision",
+        "53" => "Sammy USA Corporation",
+        "54" => "Konami",
+        "55" => "Hi Tech Expressions",
+        "56" => "LJN",
+        "57" => "Matchbox",
+        "58" => "Mattel",
+        "59" => "Milton Bradley Company",
+        "60" => "Titus Interactive",
+        "61" => "Virgin Games Ltd.",
+        "64" => "Lucasfilm Games",
+        "67" => "Ocean Software",
+        "69" => "EA (Electronic Arts)",
+        "70" => "Infogrames",
+        "71" => "Interplay Entertainment",
+        "72" => "Broderbund",
+        "73" => "Sculptured Software",
+        "75" => "The Sales Curve Limited",
+        "78" => "THQ",
+        "79" => "Accolade",
+        "80" => "Misawa Entertainment",
+        "83" => "LOZC G.",
+        "86" => "Tokuma Shoten",
+        "87" => "Tsukuda Original",
+        "91" => "Chunsoft Co.",
+        "92" => "Video System",
+        "93" => "Ocean Software/Acclaim Entertainment",
+        "95" => "Varie",
+        "96" => "Yonezawa/S'Pal",
+        "97" => "Kaneko",
+        "99" => "Pack-In-Video",
+        "9H" => "Bottom Up",
+        "A4" => "Konami (Yu-Gi-Oh!)",
+        "BL" => "MTO",
+        "DK" => "Kodansha",
+        _ => return None,
+    })
+}
+
+/// Header text is ASCII padded with zeroes; anything else is replaced so it can still be shown.
+fn to_text(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|b| **b != 0)
+        .map(|b| {
+            if b.is_ascii_graphic() || *b == b' ' {
+                *b as char
+            } else {
+                char::REPLACEMENT_CHARACTER
+            }
+        })
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    fn place(rom: &mut [u8], address: u16, bytes: &[u8]) {
+        rom[address as usize..address as usize + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// A 32 KiB ROM with a valid header and correct checksums.
+    fn make_rom() -> Vec<u8> {
+        let mut rom = vec![0; 2 * ROM_BANK_SIZE];
+        place(&mut rom, HEADER_LOGO.begin, &NINTENDO_LOGO);
+        place(&mut rom, HEADER_TITLE.begin, b"TESTGAME");
+        place(&mut rom, HEADER_NEW_LICENSEE.begin, b"01");
+        rom[HEADER_OLD_LICENSEE as usize] = USE_NEW_LICENSEE;
+        rom[HEADER_DESTINATION as usize] = 0x01;
+        rom[HEADER_VERSION as usize] = 0x02;
+        fix_checksums(&mut rom);
+        rom
+    }
+
+    fn fix_checksums(rom: &mut [u8]) {
+        rom[HEADER_CHECKSUM as usize] = compute_header_checksum(rom);
+        let global = compute_global_checksum(rom).to_be_bytes();
+        place(rom, HEADER_GLOBAL_CHECKSUM.begin, &global);
+    }
+
+    #[test]
+    fn test_parse_valid() {
+        let header = CartridgeHeader::parse(&make_rom());
+        assert_eq!(header.warnings, vec![]);
+        assert_eq!(header.title, "TESTGAME");
+        assert_eq!(header.manufacturer_code, None);
+        assert_eq!(header.cgb_support, CgbSupport::None);
+        assert_eq!(header.new_licensee_code, "01");
+        assert!(header.uses_new_licensee());
+        assert_eq!(header.destination, Destination::Overseas);
+        assert_eq!(header.version, 0x02);
+        assert_eq!(header.rom_banks, Some(2));
+        assert_eq!(header.ram_size, Some(0));
+        assert!(header.logo_valid && header.header_checksum_valid && header.global_checksum_valid);
+    }
+
+    #[test]
+    fn test_parse_cgb_title() {
+        let mut rom = make_rom();
+        place(&mut rom, HEADER_TITLE.begin, b"CGBGAME\0\0\0\0ABCD\xC0");
+        rom[HEADER_SGB_FLAG as usize] = 0x03;
+        fix_checksums(&mut rom);
+
+        let header = CartridgeHeader::parse(&rom);
+        assert_eq!(header.warnings, vec![]);
+        assert_eq!(header.title, "CGBGAME");
+        assert_eq!(header.manufacturer_code, Some("ABCD".to_string()));
+        assert_eq!(header.cgb_support, CgbSupport::Only);
+        assert!(header.sgb_support);
+    }
+
+    #[test]
+    fn test_parse_warnings() {
+        let mut rom = make_rom();
+        rom[HEADER_LOGO.begin as usize] = 0;
+        rom[HEADER_CART_TYPE as usize] = 0x04;
+        rom[HEADER_RAM_SIZE as usize] = 0x07;
+        rom.truncate(ROM_BANK_SIZE);
+
+        let header = CartridgeHeader::parse(&rom);
+        assert!(!header.logo_valid);
+        assert!(header.warnings.contains(&HeaderWarning::BadLogo));
+        assert!(
+            header
+                .warnings
+                .contains(&HeaderWarning::UnknownCartType(0x04))
+        );
+        assert!(
+            header
+                .warnings
+                .contains(&HeaderWarning::UnknownRamSize(0x07))
+        );
+        assert!(header.warnings.contains(&HeaderWarning::RomSizeMismatch {
+            header: 2 * ROM_BANK_SIZE,
+            file: ROM_BANK_SIZE
+        }));
+        assert!(!header.header_checksum_valid);
+        assert!(!header.global_checksum_valid);
+
+        let header = CartridgeHeader::parse(&rom[..0x100]);
+        assert_eq!(
+            header.warnings,
+            vec![HeaderWarning::TooShort { len: 0x100 }]
+        );
+    }
+}
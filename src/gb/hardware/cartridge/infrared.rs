@@ -0,0 +1,30 @@
+use log::debug;
+
+// Bit 0 reads as 1 while the receiver sees light; bits 6-7 always read as 1 and bits 1-5 as 0
+const IR_NO_LIGHT: u8 = 0xC0;
+const IR_LED_BIT: u8 = 0x01;
+
+/// The infrared LED and receiver on Hudson carts. Nothing is ever on the other end, so the
+/// receiver never sees any light; games treat that as no partner being present.
+#[derive(Debug, Default)]
+pub struct IrPort {
+    led_on: bool,
+}
+
+impl IrPort {
+    pub fn read(&self) -> u8 {
+        IR_NO_LIGHT
+    }
+
+    pub fn write(&mut self, value: u8) {
+        let on = value & IR_LED_BIT != 0;
+        if on != self.led_on {
+            debug!("IR LED {}", if on { "on" } else { "off" });
+            self.led_on = on;
+        }
+    }
+
+    pub fn led_on(&self) -> bool {
+        self.led_on
+    }
+}
@@ -0,0 +1,524 @@
+use crate::gb::hardware::cartridge::{CartridgeError, MAX_ROM_SIZE};
+use log::info;
+use std::{fs, path::Path};
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: usize = 0x454F46; // "EOF"
+const UPS_MAGIC: &[u8] = b"UPS1";
+const BPS_MAGIC: &[u8] = b"BPS1";
+
+// Source, target and patch CRC32s, all little endian
+const FOOTER_SIZE: usize = 12;
+
+const AUTO_PATCH_EXTENSIONS: &[&str] = &["ips", "ups", "bps"];
+
+/// Applies the given patch files to a ROM in order. If none are given, any patch sitting next to
+/// the ROM with the same name (e.g. `game.ips` for `game.gb`) is applied instead.
+pub fn apply_patches(
+    mut rom: Vec<u8>,
+    rom_path: &Path,
+    patch_paths: &[String],
+) -> Result<Vec<u8>, CartridgeError> {
+    let patch_paths: Vec<_> = if patch_paths.is_empty() {
+        AUTO_PATCH_EXTENSIONS
+            .iter()
+            .map(|ext| rom_path.with_extension(ext))
+            .filter(|path| path.is_file())
+            .collect()
+    } else {
+        patch_paths
+            .iter()
+            .map(Path::new)
+            .map(Path::to_path_buf)
+            .collect()
+    };
+
+    for path in patch_paths {
+        let patch = fs::read(&path)?;
+        rom = apply_patch(&rom, &patch)?;
+        info!("Applied patch '{}'.", path.display());
+    }
+
+    Ok(rom)
+}
+
+/// Applies a single patch, working out its format from its magic bytes.
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, CartridgeError> {
+    if patch.starts_with(IPS_MAGIC) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(UPS_MAGIC) {
+        apply_ups(rom, patch)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else {
+        Err(patch_error("unknown patch format"))
+    }
+}
+
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, CartridgeError> {
+    let mut out = rom.to_vec();
+    let mut reader = PatchReader::new(patch, IPS_MAGIC.len());
+
+    loop {
+        let offset = reader.big_endian(3)?;
+        if offset == IPS_EOF {
+            break;
+        }
+
+        let size = reader.big_endian(2)?;
+        if size == 0 {
+            // Run-length encoded record
+            let count = reader.big_endian(2)?;
+            let value = reader.byte()?;
+            write_at(&mut out, offset, &vec![value; count])?;
+        } else {
+            write_at(&mut out, offset, reader.bytes(size)?)?;
+        }
+    }
+
+    // Some patches truncate the ROM after the EOF marker
+    if reader.remaining() >= 3 {
+        out.truncate(reader.big_endian(3)?);
+    }
+
+    Ok(out)
+}
+
+fn apply_ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, CartridgeError> {
+    let footer = check_footer(rom, patch, "UPS")?;
+    let mut reader = PatchReader::new(&patch[..footer], UPS_MAGIC.len());
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    check_source_size(rom, source_size, "UPS")?;
+    check_target_size(target_size, "UPS")?;
+
+    let mut out = rom.to_vec();
+    out.resize(target_size, 0);
+
+    // Each hunk skips ahead, then XORs bytes into the ROM until a zero byte
+    let mut offset: usize = 0;
+    while reader.remaining() > 0 {
+        offset = checked_offset(offset.checked_add(reader.varint()?))?;
+        loop {
+            let x = reader.byte()?;
+            if let Some(value) = out.get_mut(offset) {
+                *value ^= x;
+            }
+            offset = checked_offset(offset.checked_add(1))?;
+            if x == 0 {
+                break;
+            }
+        }
+    }
+
+    check_target_crc(&out, patch, "UPS")?;
+    Ok(out)
+}
+
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, CartridgeError> {
+    let footer = check_footer(rom, patch, "BPS")?;
+    let mut reader = PatchReader::new(&patch[..footer], BPS_MAGIC.len());
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    let metadata_size = reader.varint()?;
+    reader.bytes(metadata_size)?;
+    check_source_size(rom, source_size, "BPS")?;
+    check_target_size(target_size, "BPS")?;
+
+    let mut out = Vec::with_capacity(target_size);
+    let mut source_offset = 0;
+    let mut target_offset = 0;
+
+    while reader.remaining() > 0 {
+        let data = reader.varint()?;
+        let length = (data >> 2) + 1;
+        if out
+            .len()
+            .checked_add(length)
+            .is_none_or(|end| end > target_size)
+        {
+            return Err(patch_error(
+                "BPS patch writes past its declared output size",
+            ));
+        }
+        match data & 0x03 {
+            // SourceRead: copy from the same position in the original ROM
+            0 => {
+                let start = out.len();
+                out.extend_from_slice(source_slice(rom, start, length)?);
+            }
+            // TargetRead: copy bytes stored in the patch
+            1 => out.extend_from_slice(reader.bytes(length)?),
+            // SourceCopy: copy from anywhere in the original ROM
+            2 => {
+                source_offset = relative_offset(source_offset, reader.varint()?)?;
+                out.extend_from_slice(source_slice(rom, source_offset, length)?);
+                source_offset += length;
+            }
+            // TargetCopy: copy from earlier in the output; the ranges can overlap
+            _ => {
+                target_offset = relative_offset(target_offset, reader.varint()?)?;
+                for _ in 0..length {
+                    match out.get(target_offset) {
+                        Some(&value) => out.push(value),
+                        None => {
+                            return Err(patch_error("BPS patch copies from past its output"));
+                        }
+                    }
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if out.len() != target_size {
+        return Err(patch_error(format!(
+            "BPS patch produced {} bytes, but declares a {target_size} byte output",
+            out.len()
+        )));
+    }
+
+    check_target_crc(&out, patch, "BPS")?;
+    Ok(out)
+}
+
+/// Checks the patch's own checksum and that it's meant for this ROM, then returns where the
+/// footer starts.
+fn check_footer(rom: &[u8], patch: &[u8], format: &str) -> Result<usize, CartridgeError> {
+    if patch.len() < FOOTER_SIZE {
+        return Err(patch_error(format!("{format} patch is too small")));
+    }
+    let footer = patch.len() - FOOTER_SIZE;
+
+    if crc32(&patch[..patch.len() - 4]) != footer_crc(patch, 2) {
+        return Err(patch_error(format!(
+            "{format} patch is corrupt (patch checksum mismatch)"
+        )));
+    }
+    if crc32(rom) != footer_crc(patch, 0) {
+        return Err(patch_error(format!(
+            "{format} patch is not for this ROM (source checksum mismatch)"
+        )));
+    }
+
+    Ok(footer)
+}
+
+fn check_source_size(rom: &[u8], size: usize, format: &str) -> Result<(), CartridgeError> {
+    if rom.len() != size {
+        return Err(patch_error(format!(
+            "{format} patch expects a {size} byte ROM, but it is {} bytes",
+            rom.len()
+        )));
+    }
+    Ok(())
+}
+
+fn check_target_size(size: usize, format: &str) -> Result<(), CartridgeError> {
+    if size > MAX_ROM_SIZE {
+        return Err(patch_error(format!(
+            "{format} patch declares a {size} byte ROM, which is bigger than any cartridge"
+        )));
+    }
+    Ok(())
+}
+
+fn check_target_crc(out: &[u8], patch: &[u8], format: &str) -> Result<(), CartridgeError> {
+    if crc32(out) != footer_crc(patch, 1) {
+        return Err(patch_error(format!(
+            "{format} patch produced the wrong ROM (target checksum mismatch)"
+        )));
+    }
+    Ok(())
+}
+
+fn patch_error(reason: impl Into<String>) -> CartridgeError {
+    CartridgeError::BadPatch(reason.into())
+}
+
+fn footer_crc(patch: &[u8], index: usize) -> u32 {
+    let start = patch.len() - FOOTER_SIZE + index * 4;
+    u32::from_le_bytes(patch[start..start + 4].try_into().unwrap())
+}
+
+fn source_slice(rom: &[u8], start: usize, length: usize) -> Result<&[u8], CartridgeError> {
+    start
+        .checked_add(length)
+        .and_then(|end| rom.get(start..end))
+        .ok_or_else(|| patch_error("BPS patch reads from past the end of the ROM"))
+}
+
+fn checked_offset(offset: Option<usize>) -> Result<usize, CartridgeError> {
+    offset.ok_or_else(|| patch_error("patch offset is out of range"))
+}
+
+/// BPS copy offsets are stored relative to the previous one, with the sign in the lowest bit.
+fn relative_offset(offset: usize, data: usize) -> Result<usize, CartridgeError> {
+    let delta = data >> 1;
+    let result = if data & 1 != 0 {
+        offset.checked_sub(delta)
+    } else {
+        offset.checked_add(delta)
+    };
+    result.ok_or_else(|| patch_error("BPS patch has an out of range copy offset"))
+}
+
+/// Grows the ROM as needed, since patches are allowed to write past its end.
+fn write_at(rom: &mut Vec<u8>, offset: usize, data: &[u8]) -> Result<(), CartridgeError> {
+    let end = checked_offset(offset.checked_add(data.len()))?;
+    if rom.len() < end {
+        rom.resize(end, 0);
+    }
+    rom[offset..end].copy_from_slice(data);
+    Ok(())
+}
+
+/// The standard (zlib/PNG) CRC32, used by UPS and BPS patches.
+pub fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xEDB88320
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    !data.iter().fold(!0, |crc, &byte| {
+        TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+struct PatchReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], CartridgeError> {
+        let end = self
+            .pos
+            .checked_add(count)
+            .ok_or_else(|| patch_error("patch file ends unexpectedly"))?;
+        let bytes = self
+            .data
+            .get(self.pos..end)
+            .ok_or_else(|| patch_error("patch file ends unexpectedly"))?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, CartridgeError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn big_endian(&mut self, count: usize) -> Result<usize, CartridgeError> {
+        Ok(self
+            .bytes(count)?
+            .iter()
+            .fold(0, |value, &byte| (value << 8) | byte as usize))
+    }
+
+    /// UPS/BPS variable-length number: 7 bits per byte, where the top bit marks the last one.
+    /// Every continuation also adds one, so each number has only one encoding.
+    fn varint(&mut self) -> Result<usize, CartridgeError> {
+        let overflow = || patch_error("patch has a number too big to read");
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.byte()?;
+            value = ((byte & 0x7F) as usize)
+                .checked_mul(shift)
+                .and_then(|part| value.checked_add(part))
+                .ok_or_else(overflow)?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_mul(0x80).ok_or_else(overflow)?;
+            value = value.checked_add(shift).ok_or_else(overflow)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    fn encode_varint(out: &mut Vec<u8>, mut value: usize) {
+        loop {
+            let bits = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                out.push(bits | 0x80);
+                return;
+            }
+            out.push(bits);
+            value -= 1;
+        }
+    }
+
+    fn add_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend(crc32(source).to_le_bytes());
+        patch.extend(crc32(target).to_le_bytes());
+        patch.extend(crc32(&patch).to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(&[]), 0);
+    }
+
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, 0x7F, 0x80, 0x4000, 123_456_789] {
+            let mut data = Vec::new();
+            encode_varint(&mut data, value);
+            assert_eq!(PatchReader::new(&data, 0).varint().unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_varint_overflow() {
+        let data = [0x7F; 20];
+        assert!(matches!(
+            PatchReader::new(&data, 0).varint(),
+            Err(CartridgeError::BadPatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_target_too_big() {
+        let source = b"abcdef".to_vec();
+        for magic in [UPS_MAGIC, BPS_MAGIC] {
+            let mut patch = magic.to_vec();
+            encode_varint(&mut patch, source.len());
+            encode_varint(&mut patch, usize::MAX >> 8);
+            encode_varint(&mut patch, 0);
+            let patch = add_footer(patch, &source, &source);
+
+            assert!(matches!(
+                apply_patch(&source, &patch),
+                Err(CartridgeError::BadPatch(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_bps_past_target() {
+        let source = b"abcdef".to_vec();
+        let mut patch = BPS_MAGIC.to_vec();
+        encode_varint(&mut patch, source.len());
+        encode_varint(&mut patch, source.len());
+        encode_varint(&mut patch, 0);
+        // A TargetCopy far longer than the declared output
+        encode_varint(&mut patch, 5 << 2);
+        encode_varint(&mut patch, (1 << 40) << 2 | 3);
+        encode_varint(&mut patch, 0);
+        let patch = add_footer(patch, &source, &source);
+
+        assert!(matches!(
+            apply_patch(&source, &patch),
+            Err(CartridgeError::BadPatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_ips() {
+        let rom = vec![0; 8];
+        let mut patch = IPS_MAGIC.to_vec();
+        // Plain record
+        patch.extend([0x00, 0x00, 0x01, 0x00, 0x02, 0xAA, 0xBB]);
+        // RLE record past the end of the ROM
+        patch.extend([0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x04, 0xCC]);
+        patch.extend(b"EOF");
+
+        let out = apply_patch(&rom, &patch).unwrap();
+        assert_eq!(out, [0, 0xAA, 0xBB, 0, 0, 0, 0xCC, 0xCC, 0xCC, 0xCC]);
+
+        // Truncation
+        patch.extend([0x00, 0x00, 0x03]);
+        assert_eq!(apply_patch(&rom, &patch).unwrap(), [0, 0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn test_ups() {
+        let source = b"Hello, world".to_vec();
+        let target = b"Hello, there!".to_vec();
+
+        let mut patch = UPS_MAGIC.to_vec();
+        encode_varint(&mut patch, source.len());
+        encode_varint(&mut patch, target.len());
+        // Skip "Hello, ", then XOR the rest
+        encode_varint(&mut patch, 7);
+        for (i, byte) in target.iter().enumerate().skip(7) {
+            patch.push(source.get(i).unwrap_or(&0) ^ byte);
+        }
+        patch.push(0);
+        let patch = add_footer(patch, &source, &target);
+
+        assert_eq!(apply_patch(&source, &patch).unwrap(), target);
+    }
+
+    #[test]
+    fn test_bps() {
+        let source = b"abcdef".to_vec();
+        let target = b"abcXYZXYZdef".to_vec();
+
+        let mut patch = BPS_MAGIC.to_vec();
+        encode_varint(&mut patch, source.len());
+        encode_varint(&mut patch, target.len());
+        encode_varint(&mut patch, 0);
+        // SourceRead "abc"
+        encode_varint(&mut patch, 2 << 2);
+        // TargetRead "XYZ"
+        encode_varint(&mut patch, (2 << 2) | 1);
+        patch.extend(b"XYZ");
+        // TargetCopy "XYZ" from offset 3
+        encode_varint(&mut patch, (2 << 2) | 3);
+        encode_varint(&mut patch, 3 << 1);
+        // SourceCopy "def" from offset 3
+        encode_varint(&mut patch, (2 << 2) | 2);
+        encode_varint(&mut patch, 3 << 1);
+        let patch = add_footer(patch, &source, &target);
+
+        assert_eq!(apply_patch(&source, &patch).unwrap(), target);
+    }
+
+    #[test]
+    fn test_bps_wrong_rom() {
+        let source = b"abcdef".to_vec();
+        let mut patch = BPS_MAGIC.to_vec();
+        encode_varint(&mut patch, source.len());
+        encode_varint(&mut patch, source.len());
+        encode_varint(&mut patch, 0);
+        encode_varint(&mut patch, 5 << 2);
+        let patch = add_footer(patch, &source, &source);
+
+        assert!(matches!(
+            apply_patch(b"abcdeg", &patch),
+            Err(CartridgeError::BadPatch(_))
+        ));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::gb::hardware::cartridge::ROM_BANK_SIZE;
+use log::{info, warn};
+
+const PAD_VALUE: u8 = 0xFF;
+
+// Even the smallest carts fill both ROM banks of the address space
+const MIN_BANKS: usize = 2;
+
+/// A cart's ROM, sized from the actual file rather than trusting the header.
+#[derive(Debug)]
+pub struct Rom {
+    data: Vec<u8>,
+}
+
+impl Rom {
+    /// Pads the image with $FF up to a whole number of banks (at least two). If the file size
+    /// disagrees with the `header_banks` the header declares, the file wins.
+    pub fn new(image: &[u8], header_banks: usize) -> Self {
+        let banks = image.len().div_ceil(ROM_BANK_SIZE).max(MIN_BANKS);
+        let size = banks * ROM_BANK_SIZE;
+
+        if banks != header_banks {
+            warn!(
+                "Header declares {header_banks} ROM banks, but the file has {banks}; using the file size."
+            );
+        }
+        if image.len() != size {
+            info!(
+                "ROM is {} bytes; padding it to {size} bytes with $FF.",
+                image.len()
+            );
+        }
+        if !banks.is_power_of_two() {
+            info!("ROM has {banks} banks, which isn't a power of two; mirroring the upper banks.");
+        }
+
+        let mut data = image.to_vec();
+        data.resize(size, PAD_VALUE);
+        Self { data }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn banks(&self) -> usize {
+        self.data.len() / ROM_BANK_SIZE
+    }
+
+    /// Reads from anywhere in the ROM, mirroring offsets past its end.
+    pub fn read(&self, offset: usize) -> u8 {
+        self.data[mirror(offset, self.data.len())]
+    }
+
+    /// Reads from `local_address` within a switchable bank.
+    pub fn read_bank(&self, bank: usize, local_address: u16) -> u8 {
+        self.read(bank * ROM_BANK_SIZE + local_address as usize)
+    }
+}
+
+impl Default for Rom {
+    fn default() -> Self {
+        Self {
+            data: vec![PAD_VALUE; MIN_BANKS * ROM_BANK_SIZE],
+        }
+    }
+}
+
+/// Odd-sized mask ROMs are built from a power-of-two chip plus smaller ones, each decoding only
+/// as many address lines as it needs. An offset past the end therefore wraps within the chip it
+/// lands on: a 3-bank ROM sees bank 3 as a copy of bank 2, not bank 0.
+fn mirror(mut offset: usize, mut size: usize) -> usize {
+    let mut base = 0;
+    let mut mask = size.next_power_of_two();
+    offset &= mask - 1;
+
+    while offset >= size {
+        while offset & mask == 0 {
+            mask >>= 1;
+        }
+        offset -= mask;
+        if size > mask {
+            size -= mask;
+            base += mask;
+        }
+        mask >>= 1;
+    }
+
+    base + offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_padding() {
+        let rom = Rom::new(&[0x12; 0x5000], 2);
+        assert_eq!(rom.len(), 2 * ROM_BANK_SIZE);
+        assert_eq!(rom.read(0x4FFF), 0x12);
+        assert_eq!(rom.read(0x5000), PAD_VALUE);
+
+        // The file size wins over the header
+        let rom = Rom::new(&[0; 4 * ROM_BANK_SIZE], 2);
+        assert_eq!(rom.banks(), 4);
+    }
+
+    #[test]
+    fn test_mirroring() {
+        // Power of two: plain wrap-around
+        let mut image = vec![0; 4 * ROM_BANK_SIZE];
+        for bank in 0..4 {
+            image[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        let rom = Rom::new(&image, 4);
+        assert_eq!(rom.read_bank(5, 0), 1);
+        assert_eq!(rom.read_bank(7, 0), 3);
+
+        // Three banks: a two-bank chip plus a one-bank chip
+        image.truncate(3 * ROM_BANK_SIZE);
+        let rom = Rom::new(&image, 4);
+        assert_eq!(rom.read_bank(2, 0), 2);
+        assert_eq!(rom.read_bank(3, 0), 2);
+        assert_eq!(rom.read_bank(4, 0), 0);
+        assert_eq!(rom.read_bank(7, 0), 2);
+    }
+
+    #[test]
+    fn test_mirror() {
+        // 6 = 4 + 2
+        let expected = [0, 1, 2, 3, 4, 5, 4, 5];
+        for (offset, expected) in expected.iter().enumerate() {
+            assert_eq!(mirror(offset, 6), *expected);
+        }
+        // 5 = 4 + 1
+        assert_eq!(mirror(5, 5), 4);
+        assert_eq!(mirror(7, 5), 4);
+    }
+}
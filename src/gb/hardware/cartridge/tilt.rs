@@ -0,0 +1,96 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+/// Tilt to feed the accelerometer, one reading per frame. The last reading is held once the
+/// script runs out, so a single reading keeps the cart tilted the same way throughout.
+#[derive(Debug, Clone)]
+pub struct TiltScript {
+    readings: Vec<(f32, f32)>,
+    frame: usize,
+}
+
+impl TiltScript {
+    /// Takes either a fixed `X,Y` tilt or the path of a script with one `X,Y` reading per line.
+    pub fn load(value: &str) -> io::Result<Self> {
+        match parse_reading(value) {
+            Some(reading) => Ok(Self::from_readings(vec![reading])),
+            None => Self::parse(&fs::read_to_string(Path::new(value))?),
+        }
+    }
+
+    /// Reads a script: one `X,Y` reading per frame, in g. Blank lines and lines starting with
+    /// `#` are skipped.
+    pub fn parse(script: &str) -> io::Result<Self> {
+        let mut readings = Vec::new();
+        for (number, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match parse_reading(line) {
+                Some(reading) => readings.push(reading),
+                None => return Err(tilt_error(&format!("bad reading on line {}", number + 1))),
+            }
+        }
+
+        if readings.is_empty() {
+            return Err(tilt_error("the script has no readings"));
+        }
+        Ok(Self::from_readings(readings))
+    }
+
+    fn from_readings(readings: Vec<(f32, f32)>) -> Self {
+        Self { readings, frame: 0 }
+    }
+
+    /// The reading for the next frame.
+    pub fn next_reading(&mut self) -> (f32, f32) {
+        let reading = self.readings[self.frame.min(self.readings.len() - 1)];
+        self.frame += 1;
+        reading
+    }
+}
+
+fn parse_reading(text: &str) -> Option<(f32, f32)> {
+    let (x, y) = text.split_once(',')?;
+    let x = x.trim().parse::<f32>().ok().filter(|x| x.is_finite())?;
+    let y = y.trim().parse::<f32>().ok().filter(|y| y.is_finite())?;
+    Some((x, y))
+}
+
+fn tilt_error(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("Tilt script: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_fixed_tilt() {
+        let mut tilt = TiltScript::load("0.5, -1").unwrap();
+        assert_eq!(tilt.next_reading(), (0.5, -1.0));
+        assert_eq!(tilt.next_reading(), (0.5, -1.0));
+
+        // Anything else is taken for a script file
+        assert!(TiltScript::load("res/missing.tilt").is_err());
+    }
+
+    #[test]
+    fn test_script() {
+        let mut tilt = TiltScript::parse("# Roll right\n0,0\n\n0.25,0\n0.5,0.1\n").unwrap();
+        assert_eq!(tilt.next_reading(), (0.0, 0.0));
+        assert_eq!(tilt.next_reading(), (0.25, 0.0));
+        assert_eq!(tilt.next_reading(), (0.5, 0.1));
+        // The last reading is held
+        assert_eq!(tilt.next_reading(), (0.5, 0.1));
+
+        assert!(TiltScript::parse("0,0\n1;1\n").is_err());
+        assert!(TiltScript::parse("0,inf\n").is_err());
+        assert!(TiltScript::parse("# Nothing\n").is_err());
+    }
+}
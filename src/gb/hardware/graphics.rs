@@ -1,32 +1,653 @@
 use crate::{
+    define_reg_bits,
     gb::{
-        GameBoy,
-        hardware::{HardwareInit, HardwareInterface, memory::OPEN_BUS_VALUE},
+        Dot, GameBoy,
+        hardware::{
+            HardwareInit, HardwareInterface,
+            processor::{
+                Processor,
+                interrupts::{STAT, VBLANK},
+            },
+        },
+        regions::{MappedMemoryRegion, OAM, VRAM},
+        registers::{
+            IO_BGP, IO_LCDC, IO_LY, IO_LYC, IO_OBP0, IO_OBP1, IO_SCX, IO_SCY, IO_STAT, IO_WX, IO_WY,
+        },
     },
-    warn_unimplemented_interface, warn_unimplemented_read, warn_unimplemented_write,
+    has_opt, impossible_address,
+    options::PIXEL_FIFO,
 };
+use log::debug;
+use num_derive::FromPrimitive;
+
+mod fifo;
+mod scanline;
+
+use fifo::PixelFifo;
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+const DOTS_PER_LINE: u16 = 456;
+const OAM_SCAN_DOTS: u16 = 80;
+// Drawing takes at least this long; fine scrolling stalls it a little longer
+const MIN_DRAWING_DOTS: u16 = 172;
+const VBLANK_START_LINE: u8 = SCREEN_HEIGHT as u8;
+const LINES_PER_FRAME: u8 = 154;
+
+const TILE_MAP_LOW: u16 = 0x9800;
+const TILE_MAP_HIGH: u16 = 0x9C00;
+const TILE_MAP_WIDTH: u16 = 32;
+// With LCDC bit 4 set, tiles are numbered 0-255 from the low base; otherwise -128-127 around
+// the high base
+const TILE_DATA_LOW: u16 = 0x8000;
+const TILE_DATA_HIGH: u16 = 0x9000;
+const TILE_SIZE: u16 = 16;
+
+// The window's left edge is at WX - 7; past this it's off screen
+const WX_OFFSET: u8 = 7;
+const WX_MAX: u8 = 166;
+
+const OAM_ENTRIES: u16 = 40;
+const OAM_ENTRY_SIZE: u16 = 4;
+const MAX_SPRITES_PER_LINE: usize = 10;
+// A sprite's OAM position is offset so it can be partly off the top or left of the screen
+const SPRITE_Y_OFFSET: i16 = 16;
+const SPRITE_X_OFFSET: i16 = 8;
+
+const SPRITE_BG_PRIORITY: u8 = 0x80;
+const SPRITE_Y_FLIP: u8 = 0x40;
+const SPRITE_X_FLIP: u8 = 0x20;
+const SPRITE_PALETTE: u8 = 0x10;
+
+// The LYC == LY flag and the mode can't be written
+const STAT_READ_ONLY_MASK: u8 = 0x07;
+const STAT_LYC_EQUAL_POS: u8 = 2;
+
+#[derive(Debug, Default, FromPrimitive, Clone, Copy, PartialEq, Eq)]
+pub enum PpuMode {
+    #[default]
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Drawing = 3,
+}
+
+/// How the picture is drawn. The scanline renderer draws each line at once when mode 3 ends; the
+/// FIFO renderer runs the real pixel pipeline dot by dot, so mid-line register writes show and
+/// mode 3 takes as long as it would on hardware.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Renderer {
+    #[default]
+    Scanline,
+    Fifo,
+}
+
+/// A whole screen of shades, row by row: 0 is the lightest and 3 the darkest, after the palettes
+/// have been applied.
+#[derive(Debug, Clone)]
+pub struct FrameBuffer {
+    pixels: Vec<u8>,
+}
+
+impl FrameBuffer {
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    fn line_mut(&mut self, ly: u8) -> &mut [u8] {
+        let start = ly as usize * SCREEN_WIDTH;
+        &mut self.pixels[start..start + SCREEN_WIDTH]
+    }
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        Self {
+            pixels: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+        }
+    }
+}
+
+/// An OAM entry picked for the current line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Sprite {
+    /// Screen position of the left edge; negative when partly off screen.
+    x: i16,
+    /// Which row of the sprite is on the current line, before flipping.
+    row: u8,
+    tile: u8,
+    attributes: u8,
+}
 
 #[derive(Debug, Default)]
 pub struct Graphics {
-    // TODO: Graphics
+    renderer: Renderer,
+
+    // LCDC
+    lcd_enabled: bool,
+    window_tile_map_high: bool,
+    window_enabled: bool,
+    bg_window_tiles_low: bool,
+    bg_tile_map_high: bool,
+    obj_tall: bool,
+    obj_enabled: bool,
+    bg_window_enabled: bool,
+
+    // STAT interrupt selects
+    lyc_int_select: bool,
+    oam_scan_int_select: bool,
+    vblank_int_select: bool,
+    hblank_int_select: bool,
+
+    // Registers
+    scy: u8,
+    scx: u8,
+    ly: u8,
+    lyc: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    wy: u8,
+    wx: u8,
+
+    // State
+    mode: PpuMode,
+    dot: u16,
+    drawing_dots: u16,
+    /// The STAT interrupt only fires when this goes from low to high.
+    stat_line: bool,
+    requested_interrupts: u8,
+    /// Set once LY has matched WY this frame; the window can't show before that.
+    window_triggered: bool,
+    /// Which line of the window is next. It only advances on lines the window was drawn on.
+    window_line: u8,
+    fifo: PixelFifo,
+
+    // Output
+    frame: FrameBuffer,
+    completed_frame: FrameBuffer,
+    frame_completed: bool,
 }
 
+define_reg_bits!(
+    for LCDC:
+        ENABLE:
+            width: 0b1;
+            pos: 7;
+            field: lcd_enabled: bool;
+            to_u8: e => { e as u8 };
+            from_u8: e => { e != 0 };
+        WINDOW_TILE_MAP:
+            width: 0b1;
+            pos: 6;
+            field: window_tile_map_high: bool;
+            to_u8: m => { m as u8 };
+            from_u8: m => { m != 0 };
+        WINDOW_ENABLE:
+            width: 0b1;
+            pos: 5;
+            field: window_enabled: bool;
+            to_u8: e => { e as u8 };
+            from_u8: e => { e != 0 };
+        BG_WINDOW_TILES:
+            width: 0b1;
+            pos: 4;
+            field: bg_window_tiles_low: bool;
+            to_u8: t => { t as u8 };
+            from_u8: t => { t != 0 };
+        BG_TILE_MAP:
+            width: 0b1;
+            pos: 3;
+            field: bg_tile_map_high: bool;
+            to_u8: m => { m as u8 };
+            from_u8: m => { m != 0 };
+        OBJ_SIZE:
+            width: 0b1;
+            pos: 2;
+            field: obj_tall: bool;
+            to_u8: t => { t as u8 };
+            from_u8: t => { t != 0 };
+        OBJ_ENABLE:
+            width: 0b1;
+            pos: 1;
+            field: obj_enabled: bool;
+            to_u8: e => { e as u8 };
+            from_u8: e => { e != 0 };
+        BG_WINDOW_ENABLE:
+            width: 0b1;
+            pos: 0;
+            field: bg_window_enabled: bool;
+            to_u8: e => { e as u8 };
+            from_u8: e => { e != 0 };
+);
+
+define_reg_bits!(
+    for STAT:
+        LYC_INT:
+            width: 0b1;
+            pos: 6;
+            field: lyc_int_select: bool;
+            to_u8: s => { s as u8 };
+            from_u8: s => { s != 0 };
+        OAM_SCAN_INT:
+            width: 0b1;
+            pos: 5;
+            field: oam_scan_int_select: bool;
+            to_u8: s => { s as u8 };
+            from_u8: s => { s != 0 };
+        VBLANK_INT:
+            width: 0b1;
+            pos: 4;
+            field: vblank_int_select: bool;
+            to_u8: s => { s as u8 };
+            from_u8: s => { s != 0 };
+        HBLANK_INT:
+            width: 0b1;
+            pos: 3;
+            field: hblank_int_select: bool;
+            to_u8: s => { s as u8 };
+            from_u8: s => { s != 0 };
+);
+
 impl HardwareInit for Graphics {
     fn init(ctx: &mut GameBoy) {
-        warn_unimplemented_interface!("Graphics");
-        // TODO: Graphics init
+        ctx.gfx = Graphics::default();
+        if has_opt!(ctx.opts, PIXEL_FIFO) {
+            ctx.gfx.renderer = Renderer::Fifo;
+        }
+
+        if ctx.skip_boot {
+            // What the boot ROM leaves behind
+            decomp_reg_LCDC!(ctx.gfx, 0x91);
+            ctx.gfx.bgp = 0xFC;
+            ctx.gfx.mode = PpuMode::OamScan;
+        }
     }
 }
 
 impl HardwareInterface for Graphics {
     fn read(ctx: &GameBoy, address: u16) -> u8 {
-        // TODO: Graphics read
-        warn_unimplemented_read!(ctx, "Graphics", address);
-        OPEN_BUS_VALUE
+        let gfx = &ctx.gfx;
+        match address {
+            IO_LCDC => make_reg_LCDC!(gfx),
+            IO_STAT => {
+                (make_reg_STAT!(gfx) & !STAT_READ_ONLY_MASK)
+                    | ((gfx.lyc_equal() as u8) << STAT_LYC_EQUAL_POS)
+                    | gfx.mode as u8
+            }
+            IO_SCY => gfx.scy,
+            IO_SCX => gfx.scx,
+            IO_LY => gfx.ly,
+            IO_LYC => gfx.lyc,
+            IO_BGP => gfx.bgp,
+            IO_OBP0 => gfx.obp0,
+            IO_OBP1 => gfx.obp1,
+            IO_WY => gfx.wy,
+            IO_WX => gfx.wx,
+
+            _ => impossible_address!("Graphics", address),
+        }
     }
 
     fn write(ctx: &mut GameBoy, address: u16, value: u8) {
-        // TODO: Graphics write
-        warn_unimplemented_write!(ctx, "Graphics", address, value)
+        let gfx = &mut ctx.gfx;
+        match address {
+            IO_LCDC => gfx.write_lcdc(value),
+            IO_STAT => decomp_reg_STAT!(gfx, value),
+            IO_SCY => gfx.scy = value,
+            IO_SCX => gfx.scx = value,
+            IO_LY => (), // Read-only
+            IO_LYC => gfx.lyc = value,
+            IO_BGP => gfx.bgp = value,
+            IO_OBP0 => gfx.obp0 = value,
+            IO_OBP1 => gfx.obp1 = value,
+            IO_WY => gfx.wy = value,
+            IO_WX => gfx.wx = value,
+
+            _ => impossible_address!("Graphics", address),
+        }
+
+        // Changing LYC or the interrupt selects can raise the STAT line right away
+        ctx.gfx.update_stat_line();
+        Graphics::flush_interrupts(ctx);
+    }
+}
+
+impl Graphics {
+    pub fn step(ctx: &mut GameBoy, time: Dot) {
+        for _ in 0..time.0 {
+            ctx.gfx.render_dot(ctx.mem.vram(), ctx.mem.oam());
+        }
+        Graphics::flush_interrupts(ctx);
+    }
+
+    /// Advances one dot, drawing with the selected renderer.
+    fn render_dot(&mut self, vram: &MappedMemoryRegion, oam: &MappedMemoryRegion) {
+        if self.renderer == Renderer::Fifo && self.mode == PpuMode::Drawing {
+            self.fifo_tick(vram);
+        }
+
+        match (self.tick(), self.renderer) {
+            (Some(PpuMode::HBlank), Renderer::Scanline) => self.render_scanline(vram, oam),
+            (Some(PpuMode::Drawing), Renderer::Fifo) => self.start_fifo_line(oam),
+            (Some(PpuMode::HBlank), Renderer::Fifo) => self.finish_fifo_line(),
+            _ => (),
+        }
+    }
+
+    pub fn mode(&self) -> PpuMode {
+        self.mode
+    }
+
+    /// Whether the PPU has the CPU locked out of `address`: OAM while it's being scanned or
+    /// drawn from, and VRAM while drawing.
+    pub fn blocks(&self, address: u16) -> bool {
+        match self.mode {
+            PpuMode::OamScan => OAM.contains(address),
+            PpuMode::Drawing => OAM.contains(address) || VRAM.contains(address),
+            PpuMode::HBlank | PpuMode::VBlank => false,
+        }
+    }
+
+    /// The last complete frame.
+    pub fn completed_frame(&self) -> &FrameBuffer {
+        &self.completed_frame
+    }
+
+    /// Returns true if a new frame has been completed since the last call.
+    pub fn take_frame_completed(&mut self) -> bool {
+        std::mem::take(&mut self.frame_completed)
+    }
+
+    fn flush_interrupts(ctx: &mut GameBoy) {
+        let requested = std::mem::take(&mut ctx.gfx.requested_interrupts);
+        if requested != 0 {
+            Processor::request_interrupt(ctx, requested);
+        }
+    }
+
+    fn write_lcdc(&mut self, value: u8) {
+        let was_enabled = self.lcd_enabled;
+        decomp_reg_LCDC!(self, value);
+
+        if was_enabled && !self.lcd_enabled {
+            // Turning the LCD off resets it to the top of the screen, in HBlank
+            debug!("LCD off");
+            self.ly = 0;
+            self.dot = 0;
+            self.mode = PpuMode::HBlank;
+        } else if !was_enabled && self.lcd_enabled {
+            debug!("LCD on");
+            self.start_frame();
+            self.set_mode(PpuMode::OamScan);
+        }
+    }
+
+    fn lyc_equal(&self) -> bool {
+        self.ly == self.lyc
+    }
+
+    /// Advances one dot. Returns the new mode if it changed.
+    fn tick(&mut self) -> Option<PpuMode> {
+        if !self.lcd_enabled {
+            return None;
+        }
+
+        self.dot += 1;
+        let next_mode = match self.mode {
+            PpuMode::OamScan if self.dot == OAM_SCAN_DOTS => Some(PpuMode::Drawing),
+            PpuMode::Drawing if self.drawing_done() => Some(PpuMode::HBlank),
+            PpuMode::HBlank | PpuMode::VBlank if self.dot == DOTS_PER_LINE => {
+                self.dot = 0;
+                self.ly = (self.ly + 1) % LINES_PER_FRAME;
+                match self.ly {
+                    0..VBLANK_START_LINE => Some(PpuMode::OamScan),
+                    VBLANK_START_LINE => Some(PpuMode::VBlank),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        if let Some(mode) = next_mode {
+            self.set_mode(mode);
+        }
+        self.update_stat_line();
+        next_mode
+    }
+
+    fn drawing_done(&self) -> bool {
+        match self.renderer {
+            Renderer::Scanline => self.dot == OAM_SCAN_DOTS + self.drawing_dots,
+            Renderer::Fifo => self.fifo.done(),
+        }
+    }
+
+    fn set_mode(&mut self, mode: PpuMode) {
+        self.mode = mode;
+        match mode {
+            PpuMode::Drawing => {
+                self.drawing_dots = MIN_DRAWING_DOTS + (self.scx % 8) as u16;
+                if self.ly == self.wy {
+                    self.window_triggered = true;
+                }
+            }
+            PpuMode::VBlank => {
+                self.requested_interrupts |= VBLANK;
+                self.completed_frame.clone_from(&self.frame);
+                self.frame_completed = true;
+                self.start_frame();
+            }
+            _ => (),
+        }
+    }
+
+    fn start_frame(&mut self) {
+        self.window_triggered = false;
+        self.window_line = 0;
+    }
+
+    /// Picks the (up to) 10 sprites on line LY, in the order they're drawn over each other: lowest
+    /// X first, then lowest OAM index.
+    fn scan_oam(&self, oam: &MappedMemoryRegion) -> Vec<Sprite> {
+        let height = if self.obj_tall { 16 } else { 8 };
+        let base = oam.region.begin;
+        let mut sprites: Vec<Sprite> = (0..OAM_ENTRIES)
+            .filter_map(|index| {
+                let entry = base + index * OAM_ENTRY_SIZE;
+                let top = oam.get(entry) as i16 - SPRITE_Y_OFFSET;
+                let row = self.ly as i16 - top;
+                (0..height).contains(&row).then(|| Sprite {
+                    x: oam.get(entry + 1) as i16 - SPRITE_X_OFFSET,
+                    row: row as u8,
+                    tile: oam.get(entry + 2),
+                    attributes: oam.get(entry + 3),
+                })
+            })
+            .take(MAX_SPRITES_PER_LINE)
+            .collect();
+        // A stable sort keeps OAM order between sprites with the same X
+        sprites.sort_by_key(|sprite| sprite.x);
+        sprites
+    }
+
+    /// The address of the tile row holding `row` of a sprite.
+    fn sprite_row_address(&self, sprite: &Sprite) -> u16 {
+        let (tile, last_row) = if self.obj_tall {
+            (sprite.tile & 0xFE, 15)
+        } else {
+            (sprite.tile, 7)
+        };
+        let row = if sprite.attributes & SPRITE_Y_FLIP != 0 {
+            last_row - sprite.row
+        } else {
+            sprite.row
+        };
+        TILE_DATA_LOW + tile as u16 * TILE_SIZE + row as u16 * 2
+    }
+
+    /// The address of the tile row for a pixel of the BG (or window) map, at `x`/`y` within the
+    /// 256x256 map.
+    fn map_row_address(&self, vram: &MappedMemoryRegion, map_high: bool, x: u8, y: u8) -> u16 {
+        let map = if map_high {
+            TILE_MAP_HIGH
+        } else {
+            TILE_MAP_LOW
+        };
+        let tile = vram.get(map + (y / 8) as u16 * TILE_MAP_WIDTH + (x / 8) as u16);
+        let tile_address = if self.bg_window_tiles_low {
+            TILE_DATA_LOW + tile as u16 * TILE_SIZE
+        } else {
+            TILE_DATA_HIGH.wrapping_add_signed(tile as i8 as i16 * TILE_SIZE as i16)
+        };
+        tile_address + (y % 8) as u16 * 2
+    }
+
+    fn update_stat_line(&mut self) {
+        let line = self.lcd_enabled
+            && ((self.lyc_int_select && self.lyc_equal())
+                || (self.oam_scan_int_select && self.mode == PpuMode::OamScan)
+                || (self.vblank_int_select && self.mode == PpuMode::VBlank)
+                || (self.hblank_int_select && self.mode == PpuMode::HBlank));
+        if line && !self.stat_line {
+            self.requested_interrupts |= STAT;
+        }
+        self.stat_line = line;
+    }
+}
+
+/// The 2-bit color of pixel `x` (0 is leftmost) of a tile row.
+fn tile_color(vram: &MappedMemoryRegion, row_address: u16, x: u8) -> u8 {
+    let bit = 7 - (x % 8);
+    let low = (vram.get(row_address) >> bit) & 1;
+    let high = (vram.get(row_address + 1) >> bit) & 1;
+    (high << 1) | low
+}
+
+fn apply_palette(palette: u8, color: u8) -> u8 {
+    (palette >> (color * 2)) & 0x03
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    fn make_gfx() -> Graphics {
+        let mut gfx = Graphics::default();
+        gfx.write_lcdc(0x91);
+        gfx
+    }
+
+    fn run(gfx: &mut Graphics, dots: u32) {
+        for _ in 0..dots {
+            gfx.tick();
+        }
+    }
+
+    #[test]
+    fn test_line_timing() {
+        let mut gfx = make_gfx();
+        assert_eq!(gfx.mode, PpuMode::OamScan);
+
+        run(&mut gfx, 79);
+        assert_eq!(gfx.mode, PpuMode::OamScan);
+        assert_eq!(gfx.tick(), Some(PpuMode::Drawing));
+        run(&mut gfx, 171);
+        assert_eq!(gfx.tick(), Some(PpuMode::HBlank));
+        run(&mut gfx, (DOTS_PER_LINE - 80 - 172 - 1) as u32);
+        assert_eq!(gfx.ly, 0);
+        assert_eq!(gfx.tick(), Some(PpuMode::OamScan));
+        assert_eq!(gfx.ly, 1);
+
+        // Fine scrolling stretches drawing
+        gfx.scx = 3;
+        run(&mut gfx, 80 + 172);
+        assert_eq!(gfx.mode, PpuMode::Drawing);
+        run(&mut gfx, 3);
+        assert_eq!(gfx.mode, PpuMode::HBlank);
+    }
+
+    #[test]
+    fn test_frame() {
+        let mut gfx = make_gfx();
+        run(&mut gfx, DOTS_PER_LINE as u32 * 144);
+        assert_eq!(gfx.ly, 144);
+        assert_eq!(gfx.mode, PpuMode::VBlank);
+        assert_eq!(gfx.requested_interrupts, VBLANK);
+        assert!(gfx.take_frame_completed());
+        assert!(!gfx.take_frame_completed());
+
+        run(&mut gfx, DOTS_PER_LINE as u32 * 9);
+        assert_eq!(gfx.ly, 153);
+        assert_eq!(gfx.mode, PpuMode::VBlank);
+        run(&mut gfx, DOTS_PER_LINE as u32);
+        assert_eq!(gfx.ly, 0);
+        assert_eq!(gfx.mode, PpuMode::OamScan);
+    }
+
+    #[test]
+    fn test_stat() {
+        let mut gfx = make_gfx();
+        gfx.lyc = 2;
+        decomp_reg_STAT!(gfx, 0x40);
+        run(&mut gfx, DOTS_PER_LINE as u32 * 2 - 1);
+        assert_eq!(gfx.requested_interrupts, 0);
+        gfx.tick();
+        assert_eq!(gfx.requested_interrupts, STAT);
+        assert_eq!(
+            (make_reg_STAT!(gfx) & !STAT_READ_ONLY_MASK) | ((gfx.lyc_equal() as u8) << 2),
+            0xC4
+        );
+
+        // The line stays high, so HBlank on the same line doesn't fire again
+        gfx.requested_interrupts = 0;
+        decomp_reg_STAT!(gfx, 0x48);
+        run(&mut gfx, 80 + 172);
+        assert_eq!(gfx.mode, PpuMode::HBlank);
+        assert_eq!(gfx.requested_interrupts, 0);
+
+        // But it does on the next line, once LYC no longer matches
+        run(&mut gfx, DOTS_PER_LINE as u32);
+        assert_eq!(gfx.requested_interrupts, STAT);
+    }
+
+    #[test]
+    fn test_lcd_off() {
+        let mut gfx = make_gfx();
+        run(&mut gfx, 1000);
+        gfx.write_lcdc(0x11);
+        assert_eq!((gfx.ly, gfx.mode), (0, PpuMode::HBlank));
+        assert_eq!(gfx.tick(), None);
+
+        gfx.write_lcdc(0x91);
+        assert_eq!(gfx.mode, PpuMode::OamScan);
+    }
+
+    #[test]
+    fn test_blocks() {
+        let mut gfx = make_gfx();
+        assert!(gfx.blocks(0xFE00) && !gfx.blocks(0x8000));
+        run(&mut gfx, 80);
+        assert_eq!(gfx.mode, PpuMode::Drawing);
+        assert!(gfx.blocks(0xFE9F) && gfx.blocks(0x9FFF));
+        assert!(!gfx.blocks(0xC000) && !gfx.blocks(0xFEA0));
+        run(&mut gfx, 172);
+        assert_eq!(gfx.mode, PpuMode::HBlank);
+        assert!(!gfx.blocks(0xFE00) && !gfx.blocks(0x8000));
+
+        // With the LCD off, everything is open
+        gfx.write_lcdc(0x11);
+        assert!(!gfx.blocks(0xFE00) && !gfx.blocks(0x8000));
+    }
+
+    #[test]
+    fn test_lcdc() {
+        let mut gfx = Graphics::default();
+        decomp_reg_LCDC!(gfx, 0b1010_0101);
+        assert!(gfx.lcd_enabled && gfx.window_enabled && gfx.obj_tall && gfx.bg_window_enabled);
+        assert!(!gfx.window_tile_map_high && !gfx.bg_window_tiles_low && !gfx.obj_enabled);
+        assert_eq!(make_reg_LCDC!(gfx), 0b1010_0101);
     }
 }
@@ -0,0 +1,386 @@
+use crate::gb::{
+    hardware::graphics::{
+        Graphics, SCREEN_WIDTH, SPRITE_BG_PRIORITY, SPRITE_PALETTE, SPRITE_X_FLIP, Sprite, WX_MAX,
+        WX_OFFSET, apply_palette, tile_color,
+    },
+    regions::MappedMemoryRegion,
+};
+use std::collections::VecDeque;
+
+// Each step of the BG fetcher takes two dots
+const FETCH_STEP_DOTS: u8 = 2;
+// Fetching a sprite's tile row stalls the pixel shifter this long, once the BG fetcher has a tile
+// ready
+const SPRITE_FETCH_DOTS: u8 = 6;
+const TILE_WIDTH: u8 = 8;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum FetchStep {
+    #[default]
+    Tile,
+    DataLow,
+    DataHigh,
+    /// Holding a tile until the BG FIFO is empty.
+    Push,
+}
+
+/// Fetches 8 pixels of BG or window at a time for the BG FIFO.
+#[derive(Debug, Default)]
+struct Fetcher {
+    step: FetchStep,
+    dots: u8,
+    /// Tiles pushed so far on this line, or since the window started.
+    tile_x: u8,
+    row_address: u16,
+    low: u8,
+    high: u8,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct SpritePixel {
+    color: u8,
+    obp1: bool,
+    bg_priority: bool,
+}
+
+/// The state of the pixel pipeline during mode 3.
+#[derive(Debug, Default)]
+pub(super) struct PixelFifo {
+    bg: VecDeque<u8>,
+    sprites: VecDeque<SpritePixel>,
+    fetcher: Fetcher,
+    /// Pixels shifted out to the LCD so far, which is also the X of the next one.
+    lx: usize,
+    /// BG pixels still to throw away instead of showing: SCX % 8 at the start of a line.
+    discard: u8,
+    /// The first tile fetched on a line is thrown away.
+    dummy_fetch: bool,
+    in_window: bool,
+    line_sprites: Vec<Sprite>,
+    next_sprite: usize,
+    sprite_dots: u8,
+}
+
+impl PixelFifo {
+    pub(super) fn done(&self) -> bool {
+        self.lx == SCREEN_WIDTH
+    }
+}
+
+impl Graphics {
+    pub(super) fn start_fifo_line(&mut self, oam: &MappedMemoryRegion) {
+        self.fifo = PixelFifo {
+            discard: self.scx % TILE_WIDTH,
+            dummy_fetch: true,
+            line_sprites: self.scan_oam(oam),
+            ..Default::default()
+        };
+    }
+
+    pub(super) fn finish_fifo_line(&mut self) {
+        if self.fifo.in_window {
+            self.window_line += 1;
+        }
+    }
+
+    /// Runs the pixel pipeline for one dot of mode 3.
+    pub(super) fn fifo_tick(&mut self, vram: &MappedMemoryRegion) {
+        if self.fifo.done() {
+            return;
+        }
+
+        if self.window_starts() {
+            // The window throws away what the BG fetcher had and starts over from its own map
+            self.fifo.in_window = true;
+            self.fifo.bg.clear();
+            self.fifo.fetcher = Fetcher::default();
+            self.fifo.discard = WX_OFFSET.saturating_sub(self.wx);
+        }
+
+        if self.sprite_due() {
+            // The shifter stalls; the BG fetcher carries on until it has a tile ready
+            if self.fifo.fetcher.step != FetchStep::Push {
+                self.fetcher_tick(vram);
+            }
+            if self.fifo.fetcher.step == FetchStep::Push {
+                self.fifo.sprite_dots += 1;
+                if self.fifo.sprite_dots == SPRITE_FETCH_DOTS {
+                    self.fetch_sprite(vram);
+                }
+            }
+            return;
+        }
+
+        self.shift_pixel();
+        self.fetcher_tick(vram);
+    }
+
+    fn window_starts(&self) -> bool {
+        let at_left_edge = match self.wx.checked_sub(WX_OFFSET) {
+            Some(left) => self.fifo.lx == left as usize,
+            None => self.fifo.lx == 0,
+        };
+        !self.fifo.in_window
+            && self.window_enabled
+            && self.bg_window_enabled
+            && self.window_triggered
+            && self.wx <= WX_MAX
+            && at_left_edge
+    }
+
+    fn sprite_due(&self) -> bool {
+        self.obj_enabled
+            && self.fifo.discard == 0
+            && self
+                .fifo
+                .line_sprites
+                .get(self.fifo.next_sprite)
+                .is_some_and(|sprite| sprite.x <= self.fifo.lx as i16)
+    }
+
+    /// Mixes the next sprite into the sprite FIFO. Pixels already there win, since sprites are
+    /// fetched in priority order.
+    fn fetch_sprite(&mut self, vram: &MappedMemoryRegion) {
+        let sprite = self.fifo.line_sprites[self.fifo.next_sprite];
+        self.fifo.next_sprite += 1;
+        self.fifo.sprite_dots = 0;
+
+        let row_address = self.sprite_row_address(&sprite);
+        // Columns already left of the screen are cut off
+        let skip = (self.fifo.lx as i16 - sprite.x) as u8;
+        for column in skip..TILE_WIDTH {
+            let tile_x = if sprite.attributes & SPRITE_X_FLIP != 0 {
+                TILE_WIDTH - 1 - column
+            } else {
+                column
+            };
+            let pixel = SpritePixel {
+                color: tile_color(vram, row_address, tile_x),
+                obp1: sprite.attributes & SPRITE_PALETTE != 0,
+                bg_priority: sprite.attributes & SPRITE_BG_PRIORITY != 0,
+            };
+
+            let position = (column - skip) as usize;
+            match self.fifo.sprites.get_mut(position) {
+                Some(existing) if existing.color == 0 => *existing = pixel,
+                Some(_) => (),
+                None => self.fifo.sprites.push_back(pixel),
+            }
+        }
+    }
+
+    /// Sends one pixel to the LCD, mixing the BG and sprite FIFOs. The palettes and enable bits
+    /// are read now, so writes to them mid-line take effect right away.
+    fn shift_pixel(&mut self) {
+        let Some(bg_color) = self.fifo.bg.pop_front() else {
+            return;
+        };
+        let sprite = self.fifo.sprites.pop_front();
+        if self.fifo.discard > 0 {
+            self.fifo.discard -= 1;
+            return;
+        }
+
+        let bg_color = if self.bg_window_enabled { bg_color } else { 0 };
+        let shade = match sprite {
+            Some(sprite)
+                if sprite.color != 0
+                    && self.obj_enabled
+                    && !(sprite.bg_priority && bg_color != 0) =>
+            {
+                let palette = if sprite.obp1 { self.obp1 } else { self.obp0 };
+                apply_palette(palette, sprite.color)
+            }
+            _ => apply_palette(self.bgp, bg_color),
+        };
+        self.frame.line_mut(self.ly)[self.fifo.lx] = shade;
+        self.fifo.lx += 1;
+    }
+
+    fn fetcher_tick(&mut self, vram: &MappedMemoryRegion) {
+        if self.fifo.fetcher.step != FetchStep::Push {
+            self.fifo.fetcher.dots += 1;
+            if self.fifo.fetcher.dots < FETCH_STEP_DOTS {
+                return;
+            }
+            self.fifo.fetcher.dots = 0;
+
+            match self.fifo.fetcher.step {
+                FetchStep::Tile => {
+                    self.fifo.fetcher.row_address = self.fetcher_row_address(vram);
+                    self.fifo.fetcher.step = FetchStep::DataLow;
+                }
+                FetchStep::DataLow => {
+                    self.fifo.fetcher.low = vram.get(self.fifo.fetcher.row_address);
+                    self.fifo.fetcher.step = FetchStep::DataHigh;
+                }
+                FetchStep::DataHigh => {
+                    self.fifo.fetcher.high = vram.get(self.fifo.fetcher.row_address + 1);
+                    self.fifo.fetcher.step = FetchStep::Push;
+                }
+                FetchStep::Push => (),
+            }
+        }
+
+        if self.fifo.fetcher.step == FetchStep::Push && self.fifo.bg.is_empty() {
+            let fetcher = &mut self.fifo.fetcher;
+            if self.fifo.dummy_fetch {
+                self.fifo.dummy_fetch = false;
+            } else {
+                for bit in (0..TILE_WIDTH).rev() {
+                    let color = (((fetcher.high >> bit) & 1) << 1) | ((fetcher.low >> bit) & 1);
+                    self.fifo.bg.push_back(color);
+                }
+                fetcher.tile_x = fetcher.tile_x.wrapping_add(1);
+            }
+            fetcher.step = FetchStep::Tile;
+        }
+    }
+
+    /// The tile row the fetcher is on, going by the scroll registers as they are now.
+    fn fetcher_row_address(&self, vram: &MappedMemoryRegion) -> u16 {
+        let tile_x = self.fifo.fetcher.tile_x.wrapping_mul(TILE_WIDTH);
+        if self.fifo.in_window {
+            self.map_row_address(vram, self.window_tile_map_high, tile_x, self.window_line)
+        } else {
+            let x = (self.scx & !(TILE_WIDTH - 1)).wrapping_add(tile_x);
+            let y = self.ly.wrapping_add(self.scy);
+            self.map_row_address(vram, self.bg_tile_map_high, x, y)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gb::{
+        hardware::graphics::{PpuMode, Renderer},
+        regions::{OAM, VRAM},
+    };
+    use test_log::test;
+
+    /// Makes a scene with a checkered BG, a window and a few sprites.
+    fn setup(renderer: Renderer) -> (Graphics, MappedMemoryRegion, MappedMemoryRegion) {
+        let mut gfx = Graphics {
+            renderer,
+            ..Default::default()
+        };
+        gfx.write_lcdc(0x93);
+        gfx.bgp = 0xE4;
+        gfx.obp0 = 0xE4;
+        gfx.obp1 = 0x1B;
+
+        let mut vram = MappedMemoryRegion::new(VRAM);
+        vram.fill(0);
+        for tile in 1..4u16 {
+            for row in 0..8 {
+                let address = 0x8000 + tile * 16 + row * 2;
+                vram.set(address, (0xF0 >> row) as u8 | tile as u8);
+                vram.set(address + 1, 0x3C ^ tile as u8);
+            }
+        }
+        for index in 0..0x400u16 {
+            vram.set(0x9800 + index, (index % 3 + 1) as u8);
+            vram.set(0x9C00 + index, (index % 2 + 2) as u8);
+        }
+
+        let mut oam = MappedMemoryRegion::new(OAM);
+        oam.fill(0);
+        (gfx, vram, oam)
+    }
+
+    fn set_sprite(oam: &mut MappedMemoryRegion, index: u16, y: u8, x: u8, tile: u8, attr: u8) {
+        let entry = OAM.begin + index * 4;
+        oam.set(entry, y);
+        oam.set(entry + 1, x);
+        oam.set(entry + 2, tile);
+        oam.set(entry + 3, attr);
+    }
+
+    /// Runs until the end of mode 3 on the current line. Returns how many dots mode 3 took.
+    fn draw_line(gfx: &mut Graphics, vram: &MappedMemoryRegion, oam: &MappedMemoryRegion) -> u32 {
+        while gfx.mode != PpuMode::Drawing {
+            gfx.render_dot(vram, oam);
+        }
+        let mut dots = 0;
+        while gfx.mode == PpuMode::Drawing {
+            gfx.render_dot(vram, oam);
+            dots += 1;
+        }
+        dots
+    }
+
+    fn line(gfx: &Graphics, ly: u8) -> &[u8] {
+        let start = ly as usize * SCREEN_WIDTH;
+        &gfx.frame.pixels()[start..start + SCREEN_WIDTH]
+    }
+
+    #[test]
+    fn test_mode_3_length() {
+        let (mut gfx, vram, mut oam) = setup(Renderer::Fifo);
+        assert_eq!(draw_line(&mut gfx, &vram, &oam), 172);
+
+        // Fine scrolling throws away pixels first
+        gfx.scx = 5;
+        assert_eq!(draw_line(&mut gfx, &vram, &oam), 177);
+
+        // A sprite at the left edge waits for two BG fetches
+        gfx.scx = 0;
+        set_sprite(&mut oam, 0, 16 + 2, 8, 0x01, 0);
+        assert_eq!(draw_line(&mut gfx, &vram, &oam), 172 + 11);
+
+        // The window restarts the fetcher
+        set_sprite(&mut oam, 0, 0, 0, 0x00, 0);
+        gfx.window_enabled = true;
+        gfx.wx = 7 + 80;
+        assert_eq!(draw_line(&mut gfx, &vram, &oam), 172 + 6);
+    }
+
+    #[test]
+    fn test_matches_scanline() {
+        let scene = |renderer| {
+            let (mut gfx, vram, mut oam) = setup(renderer);
+            gfx.scx = 13;
+            gfx.scy = 250;
+            gfx.window_enabled = true;
+            gfx.wy = 40;
+            gfx.wx = 90;
+            set_sprite(&mut oam, 0, 16, 4, 0x01, 0);
+            set_sprite(&mut oam, 1, 20, 50, 0x02, SPRITE_X_FLIP | SPRITE_PALETTE);
+            set_sprite(&mut oam, 2, 24, 54, 0x03, SPRITE_BG_PRIORITY);
+            set_sprite(&mut oam, 3, 50, 100, 0x02, 0);
+            set_sprite(&mut oam, 4, 60, 165, 0x03, 0);
+            while !gfx.take_frame_completed() {
+                gfx.render_dot(&vram, &oam);
+            }
+            gfx.completed_frame().clone()
+        };
+
+        let scanline = scene(Renderer::Scanline);
+        let fifo = scene(Renderer::Fifo);
+        for ly in 0..144 {
+            let start = ly * SCREEN_WIDTH;
+            assert_eq!(
+                &fifo.pixels()[start..start + SCREEN_WIDTH],
+                &scanline.pixels()[start..start + SCREEN_WIDTH],
+                "line {ly}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_mid_line_palette() {
+        let (mut gfx, vram, oam) = setup(Renderer::Fifo);
+        while gfx.mode != PpuMode::Drawing {
+            gfx.render_dot(&vram, &oam);
+        }
+        while gfx.fifo.lx < 80 {
+            gfx.render_dot(&vram, &oam);
+        }
+        gfx.bgp = 0x00;
+        draw_line(&mut gfx, &vram, &oam);
+
+        assert!(line(&gfx, 0)[..80].iter().any(|&p| p != 0));
+        assert!(line(&gfx, 0)[80..].iter().all(|&p| p == 0));
+    }
+}
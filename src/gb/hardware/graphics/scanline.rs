@@ -0,0 +1,293 @@
+use crate::gb::{
+    hardware::graphics::{
+        Graphics, SCREEN_WIDTH, SPRITE_BG_PRIORITY, SPRITE_PALETTE, SPRITE_X_FLIP, WX_MAX,
+        WX_OFFSET, apply_palette, tile_color,
+    },
+    regions::MappedMemoryRegion,
+};
+
+impl Graphics {
+    /// Draws the whole of line LY at once, at the end of mode 3. Mid-line register changes are
+    /// not seen, but it's cheap and right for almost every game.
+    pub(super) fn render_scanline(&mut self, vram: &MappedMemoryRegion, oam: &MappedMemoryRegion) {
+        // The raw BG/window colors, before the palette, decide sprite priority
+        let mut bg_colors = [0u8; SCREEN_WIDTH];
+
+        if self.bg_window_enabled {
+            let y = self.ly.wrapping_add(self.scy);
+            for (x, color) in bg_colors.iter_mut().enumerate() {
+                let x = (x as u8).wrapping_add(self.scx);
+                let row_address = self.map_row_address(vram, self.bg_tile_map_high, x, y);
+                *color = tile_color(vram, row_address, x);
+            }
+
+            if self.window_enabled && self.window_triggered && self.wx <= WX_MAX {
+                let left = self.wx as usize;
+                let y = self.window_line;
+                for (x, color) in bg_colors.iter_mut().enumerate() {
+                    // Written so that WX < 7 shifts the window partly off the left edge
+                    if x + (WX_OFFSET as usize) < left {
+                        continue;
+                    }
+                    let x = (x + WX_OFFSET as usize - left) as u8;
+                    let row_address = self.map_row_address(vram, self.window_tile_map_high, x, y);
+                    *color = tile_color(vram, row_address, x);
+                }
+                self.window_line += 1;
+            }
+        }
+
+        let bgp = self.bgp;
+        let line = self.frame.line_mut(self.ly);
+        for (pixel, &color) in line.iter_mut().zip(bg_colors.iter()) {
+            *pixel = apply_palette(bgp, color);
+        }
+
+        if !self.obj_enabled {
+            return;
+        }
+
+        // The first sprite in the list with an opaque pixel wins it, even if the BG then hides
+        // that pixel
+        let mut claimed = [false; SCREEN_WIDTH];
+        for sprite in self.scan_oam(oam) {
+            let row_address = self.sprite_row_address(&sprite);
+            let palette = if sprite.attributes & SPRITE_PALETTE != 0 {
+                self.obp1
+            } else {
+                self.obp0
+            };
+            let line = self.frame.line_mut(self.ly);
+
+            for column in 0..8u8 {
+                let x = sprite.x + column as i16;
+                if !(0..SCREEN_WIDTH as i16).contains(&x) {
+                    continue;
+                }
+                let x = x as usize;
+                if claimed[x] {
+                    continue;
+                }
+
+                let tile_x = if sprite.attributes & SPRITE_X_FLIP != 0 {
+                    7 - column
+                } else {
+                    column
+                };
+                let color = tile_color(vram, row_address, tile_x);
+                if color == 0 {
+                    continue;
+                }
+                claimed[x] = true;
+                if sprite.attributes & SPRITE_BG_PRIORITY != 0 && bg_colors[x] != 0 {
+                    continue;
+                }
+                line[x] = apply_palette(palette, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gb::{
+        hardware::graphics::{PpuMode, SPRITE_Y_FLIP},
+        regions::{OAM, VRAM},
+    };
+    use test_log::test;
+
+    /// Fills a tile with a single color.
+    fn fill_tile(vram: &mut MappedMemoryRegion, address: u16, color: u8) {
+        for row in 0..8 {
+            let row_address = address + row * 2;
+            vram.set(row_address, if color & 1 != 0 { 0xFF } else { 0x00 });
+            vram.set(row_address + 1, if color & 2 != 0 { 0xFF } else { 0x00 });
+        }
+    }
+
+    fn set_sprite(oam: &mut MappedMemoryRegion, index: u16, y: u8, x: u8, tile: u8, attr: u8) {
+        let entry = OAM.begin + index * 4;
+        oam.set(entry, y);
+        oam.set(entry + 1, x);
+        oam.set(entry + 2, tile);
+        oam.set(entry + 3, attr);
+    }
+
+    fn setup() -> (Graphics, MappedMemoryRegion, MappedMemoryRegion) {
+        let mut gfx = Graphics::default();
+        // LCD on, unsigned tile data, BG and sprites on; identity palettes
+        gfx.write_lcdc(0x93);
+        gfx.bgp = 0xE4;
+        gfx.obp0 = 0xE4;
+        gfx.obp1 = 0x1B;
+        let mut vram = MappedMemoryRegion::new(VRAM);
+        vram.fill(0);
+        let mut oam = MappedMemoryRegion::new(OAM);
+        oam.fill(0);
+        (gfx, vram, oam)
+    }
+
+    fn line(gfx: &Graphics, ly: u8) -> &[u8] {
+        let start = ly as usize * SCREEN_WIDTH;
+        &gfx.frame.pixels()[start..start + SCREEN_WIDTH]
+    }
+
+    #[test]
+    fn test_bg_scroll() {
+        let (mut gfx, mut vram, oam) = setup();
+        fill_tile(&mut vram, 0x8010, 3);
+        // Tile 1 at map column 1, row 1
+        vram.set(0x9800 + 32 + 1, 0x01);
+
+        gfx.ly = 0;
+        gfx.scy = 8;
+        gfx.scx = 4;
+        gfx.render_scanline(&vram, &oam);
+        assert_eq!(&line(&gfx, 0)[..12], &[0, 0, 0, 0, 3, 3, 3, 3, 3, 3, 3, 3]);
+        assert_eq!(line(&gfx, 0)[12], 0);
+
+        // Scrolling wraps around the 256x256 map
+        gfx.scx = 252;
+        gfx.render_scanline(&vram, &oam);
+        assert_eq!(line(&gfx, 0)[12], 3);
+
+        // Signed addressing puts tile 1 at 0x9010
+        gfx.bg_window_tiles_low = false;
+        gfx.render_scanline(&vram, &oam);
+        assert_eq!(line(&gfx, 0)[12], 0);
+        fill_tile(&mut vram, 0x9010, 2);
+        gfx.render_scanline(&vram, &oam);
+        assert_eq!(line(&gfx, 0)[12], 2);
+
+        // With BG off, only color 0 shows
+        gfx.bg_window_enabled = false;
+        gfx.render_scanline(&vram, &oam);
+        assert!(line(&gfx, 0).iter().all(|&p| p == 0));
+    }
+
+    #[test]
+    fn test_window() {
+        let (mut gfx, mut vram, oam) = setup();
+        fill_tile(&mut vram, 0x8010, 1);
+        fill_tile(&mut vram, 0x8020, 2);
+        // Window map at 0x9C00: tile 1 on row 0, tile 2 on row 1
+        vram.set(0x9C00, 0x01);
+        vram.set(0x9C00 + 32, 0x02);
+        gfx.window_enabled = true;
+        gfx.window_tile_map_high = true;
+        gfx.wy = 10;
+        gfx.wx = 7 + 100;
+
+        // Not triggered before LY reaches WY
+        gfx.ly = 9;
+        gfx.set_mode(PpuMode::Drawing);
+        gfx.render_scanline(&vram, &oam);
+        assert_eq!(line(&gfx, 9)[100], 0);
+
+        gfx.ly = 10;
+        gfx.set_mode(PpuMode::Drawing);
+        gfx.render_scanline(&vram, &oam);
+        assert_eq!(line(&gfx, 10)[99], 0);
+        assert_eq!(line(&gfx, 10)[100], 1);
+        assert_eq!(gfx.window_line, 1);
+
+        // Lines with the window hidden don't advance its counter
+        gfx.wx = 200;
+        for ly in 11..20 {
+            gfx.ly = ly;
+            gfx.render_scanline(&vram, &oam);
+        }
+        assert_eq!(gfx.window_line, 1);
+        gfx.wx = 7;
+        for ly in 20..28 {
+            gfx.ly = ly;
+            gfx.render_scanline(&vram, &oam);
+        }
+        assert_eq!(line(&gfx, 26)[0], 1);
+        assert_eq!(line(&gfx, 27)[0], 2);
+
+        // VBlank starts the next frame from the top of the window
+        gfx.set_mode(PpuMode::VBlank);
+        assert_eq!(gfx.window_line, 0);
+        assert!(!gfx.window_triggered);
+    }
+
+    #[test]
+    fn test_sprites() {
+        let (mut gfx, mut vram, mut oam) = setup();
+        // Tile 1: only the leftmost column is color 1, the rest transparent
+        for row in 0..8 {
+            vram.set(0x8010 + row * 2, 0x80);
+        }
+        fill_tile(&mut vram, 0x8020, 2);
+        gfx.ly = 0;
+
+        set_sprite(&mut oam, 0, 16, 8, 0x01, 0);
+        set_sprite(&mut oam, 1, 16, 20, 0x01, SPRITE_X_FLIP);
+        set_sprite(&mut oam, 2, 16, 30, 0x02, SPRITE_PALETTE);
+        gfx.render_scanline(&vram, &oam);
+        assert_eq!(&line(&gfx, 0)[..2], &[1, 0]);
+        assert_eq!(&line(&gfx, 0)[12..20], &[0, 0, 0, 0, 0, 0, 0, 1]);
+        // OBP1 maps color 2 to 1
+        assert_eq!(line(&gfx, 0)[22], 1);
+
+        // Lower X wins, then lower OAM index
+        set_sprite(&mut oam, 3, 16, 29, 0x02, 0);
+        gfx.render_scanline(&vram, &oam);
+        assert_eq!(line(&gfx, 0)[21], 2);
+        assert_eq!(line(&gfx, 0)[22], 2);
+        set_sprite(&mut oam, 3, 16, 30, 0x02, 0);
+        gfx.render_scanline(&vram, &oam);
+        assert_eq!(line(&gfx, 0)[22], 1);
+
+        // BG-over-OBJ only hides the sprite where the BG isn't color 0
+        fill_tile(&mut vram, 0x8000, 3);
+        set_sprite(&mut oam, 2, 16, 30, 0x02, SPRITE_BG_PRIORITY);
+        set_sprite(&mut oam, 3, 0, 0, 0x00, 0);
+        gfx.render_scanline(&vram, &oam);
+        assert_eq!(line(&gfx, 0)[22], 3);
+
+        // Sprites are off
+        gfx.obj_enabled = false;
+        fill_tile(&mut vram, 0x8000, 0);
+        gfx.render_scanline(&vram, &oam);
+        assert!(line(&gfx, 0).iter().all(|&p| p == 0));
+    }
+
+    #[test]
+    fn test_tall_sprites() {
+        let (mut gfx, mut vram, mut oam) = setup();
+        gfx.obj_tall = true;
+        fill_tile(&mut vram, 0x8040, 1);
+        fill_tile(&mut vram, 0x8050, 2);
+        // The low bit of the tile index is ignored
+        set_sprite(&mut oam, 0, 16, 8, 0x05, 0);
+        set_sprite(&mut oam, 1, 16, 16, 0x04, SPRITE_Y_FLIP);
+
+        gfx.ly = 0;
+        gfx.render_scanline(&vram, &oam);
+        assert_eq!(&line(&gfx, 0)[..9], &[1, 1, 1, 1, 1, 1, 1, 1, 2]);
+        gfx.ly = 15;
+        gfx.render_scanline(&vram, &oam);
+        assert_eq!(&line(&gfx, 15)[..9], &[2, 2, 2, 2, 2, 2, 2, 2, 1]);
+        gfx.ly = 16;
+        gfx.render_scanline(&vram, &oam);
+        assert!(line(&gfx, 16).iter().all(|&p| p == 0));
+    }
+
+    #[test]
+    fn test_sprite_limit() {
+        let (mut gfx, mut vram, mut oam) = setup();
+        fill_tile(&mut vram, 0x8010, 3);
+        // Sprites beyond the 10th on a line are dropped, even when off screen
+        for index in 0..12 {
+            let x = if index == 0 { 0 } else { 8 + index as u8 * 8 };
+            set_sprite(&mut oam, index, 16, x, 0x01, 0);
+        }
+        gfx.ly = 0;
+        gfx.render_scanline(&vram, &oam);
+        assert_eq!(line(&gfx, 0)[8 * 9], 3);
+        assert_eq!(line(&gfx, 0)[8 * 10], 0);
+    }
+}
@@ -3,18 +3,22 @@ use crate::{
     gb::{
         GameBoy,
         hardware::{
-            HardwareInterface, audio::Audio, graphics::Graphics, input::Input, serial::Serial,
-            timer::Timer,
+            HardwareInterface, audio::Audio, graphics::Graphics, input::Input, memory::dma::Dma,
+            serial::Serial, timer::Timer,
         },
         regions::{
             BOOT_ROM_AREA, CART_RAM, ECHO_RAM, HIGH_RAM, MappedMemoryRegion, OAM, ROM_SPACE, VRAM,
             WORK_RAM,
         },
-        registers::{IO_AUDIO, IO_BANK, IO_GRAPHICS, IO_IE, IO_IF, IO_JOYP, IO_SERIAL, IO_TIMER},
+        registers::{
+            IO_AUDIO, IO_BANK, IO_DMA, IO_GRAPHICS, IO_IE, IO_IF, IO_JOYP, IO_SERIAL, IO_TIMER,
+        },
     },
     get_bits_of, set_bits_of, word_fmt,
 };
 
+pub mod dma;
+
 pub const OPEN_BUS_VALUE: u8 = 0xFF;
 pub const UNINIT_VALUE: u8 = 0xFF;
 
@@ -34,6 +38,7 @@ pub struct Memory {
 
     // State
     boot_mode: bool,
+    dma: Dma,
 }
 
 impl Default for Memory {
@@ -46,6 +51,7 @@ impl Default for Memory {
             io_if: UNINIT_VALUE,
             io_ie: UNINIT_VALUE,
             boot_mode: false,
+            dma: Dma::default(),
         }
     }
 }
@@ -76,6 +82,26 @@ impl Memory {
     }
 
     pub fn read(ctx: &GameBoy, address: u16) -> u8 {
+        if Memory::blocked(ctx, address) {
+            return OPEN_BUS_VALUE;
+        }
+        Memory::read_bus(ctx, address)
+    }
+
+    /// Reads any address regardless of what the PPU or DMA are doing, so debugging tools can look
+    /// at VRAM and OAM at any time.
+    #[cfg(debug_assertions)]
+    pub fn peek(ctx: &GameBoy, address: u16) -> u8 {
+        Memory::read_bus(ctx, address)
+    }
+
+    /// Whether the CPU is locked out of `address` by OAM DMA or by the PPU's mode.
+    fn blocked(ctx: &GameBoy, address: u16) -> bool {
+        ctx.mem.dma.blocks(address) || ctx.gfx.blocks(address)
+    }
+
+    /// Reads without the CPU's lockouts, for OAM DMA and debugging.
+    fn read_bus(ctx: &GameBoy, address: u16) -> u8 {
         if ctx.mem.boot_mode {
             if BOOT_ROM_AREA.contains(address) {
                 // Return early (BOOT ROM "maps over" everything else)
@@ -100,6 +126,7 @@ impl Memory {
                 #IO_TIMER    => Timer::read(ctx, address),
                 IO_IF        => get_bits_of!(ctx.mem.io_if, 0x1F),
                 #IO_AUDIO    => Audio::read(ctx, address),
+                IO_DMA       => Dma::read(ctx, address),
                 #IO_GRAPHICS => Graphics::read(ctx, address),
                 IO_IE        => ctx.mem.io_ie,
 
@@ -109,6 +136,10 @@ impl Memory {
     }
 
     pub fn write(ctx: &mut GameBoy, address: u16, value: u8) {
+        if Memory::blocked(ctx, address) {
+            return;
+        }
+
         if ctx.mem.boot_mode {
             if BOOT_ROM_AREA.contains(address) {
                 error_panic!(
@@ -136,6 +167,7 @@ impl Memory {
                 #IO_TIMER    => Timer::write(ctx, address, value),
                 IO_IF        => ctx.mem.io_if = set_bits_of!(ctx.mem.io_if, value, 0x1F),
                 #IO_AUDIO    => Audio::write(ctx, address, value),
+                IO_DMA       => Dma::write(ctx, address, value),
                 #IO_GRAPHICS => Graphics::write(ctx, address, value),
                 IO_BANK      => if value != 0 { ctx.mem.boot_mode = false },
                 IO_IE        => ctx.mem.io_ie = value,
@@ -145,6 +177,16 @@ impl Memory {
         }
     }
 
+    /// VRAM as the PPU sees it.
+    pub fn vram(&self) -> &MappedMemoryRegion {
+        &self.vram
+    }
+
+    /// OAM as the PPU sees it.
+    pub fn oam(&self) -> &MappedMemoryRegion {
+        &self.oam
+    }
+
     pub fn write_masked(ctx: &mut GameBoy, address: u16, value: u8, mask: u8) {
         Memory::write(
             ctx,
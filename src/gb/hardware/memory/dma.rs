@@ -0,0 +1,161 @@
+use crate::{
+    byte_fmt,
+    gb::{
+        GameBoy, MTime,
+        hardware::{
+            HardwareInterface,
+            memory::{ECHO_RAM_OFFSET, Memory},
+        },
+        regions::{IO_REGS, OAM},
+        registers::IO_DMA,
+    },
+    impossible_address,
+};
+use log::debug;
+
+const DMA_LENGTH: u16 = OAM.size();
+// A write to FF46 takes a cycle to set up before the first byte is copied
+const DMA_START_DELAY: u8 = 1;
+// DMA can't read OAM, IO or HRAM; sources past echo RAM keep reading WRAM instead
+const DMA_ECHO_END: u16 = 0xFE00;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Transfer {
+    source: u16,
+    index: u16,
+}
+
+/// OAM DMA: copies 160 bytes into OAM, one per M-cycle, while the CPU can only reach HRAM (and the
+/// IO registers, which sit on the same internal bus).
+#[derive(Debug, Default)]
+pub struct Dma {
+    /// The last value written to FF46.
+    register: u8,
+    /// Cycles until a newly requested transfer starts.
+    starting: Option<u8>,
+    transfer: Option<Transfer>,
+}
+
+impl HardwareInterface for Dma {
+    fn read(ctx: &GameBoy, address: u16) -> u8 {
+        match address {
+            IO_DMA => ctx.mem.dma.register,
+            _ => impossible_address!("DMA", address),
+        }
+    }
+
+    fn write(ctx: &mut GameBoy, address: u16, value: u8) {
+        match address {
+            IO_DMA => ctx.mem.dma.request(value),
+            _ => impossible_address!("DMA", address),
+        }
+    }
+}
+
+impl Dma {
+    pub fn step(ctx: &mut GameBoy, time: MTime) {
+        for _ in 0..time.0 {
+            if let Some((source, destination)) = ctx.mem.dma.tick() {
+                let value = Memory::read_bus(ctx, source);
+                ctx.mem.oam.set(destination, value);
+            }
+        }
+    }
+
+    /// Whether the CPU is locked out of `address` by a running transfer.
+    pub fn blocks(&self, address: u16) -> bool {
+        self.transfer.is_some() && address < IO_REGS.begin
+    }
+
+    fn request(&mut self, value: u8) {
+        debug!("OAM DMA requested from {}00", byte_fmt!(value));
+        // A transfer already running carries on (and keeps the bus) until the new one starts
+        self.register = value;
+        self.starting = Some(DMA_START_DELAY);
+    }
+
+    /// Advances one M-cycle. Returns the source and destination of the byte to copy, if any.
+    fn tick(&mut self) -> Option<(u16, u16)> {
+        match self.starting {
+            Some(0) => {
+                self.starting = None;
+                let mut source = (self.register as u16) << 8;
+                if source >= DMA_ECHO_END {
+                    source -= ECHO_RAM_OFFSET;
+                }
+                self.transfer = Some(Transfer { source, index: 0 });
+            }
+            Some(delay) => self.starting = Some(delay - 1),
+            None => (),
+        }
+
+        let transfer = self.transfer.as_mut()?;
+        let copy = (transfer.source + transfer.index, OAM.begin + transfer.index);
+        transfer.index += 1;
+        if transfer.index == DMA_LENGTH {
+            self.transfer = None;
+        }
+        Some(copy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gb::regions::HIGH_RAM;
+    use test_log::test;
+
+    #[test]
+    fn test_transfer() {
+        let mut dma = Dma::default();
+        dma.request(0xC1);
+        // The bus stays free while the transfer sets up
+        assert_eq!(dma.tick(), None);
+        assert!(!dma.blocks(0xC000));
+
+        assert_eq!(dma.tick(), Some((0xC100, 0xFE00)));
+        assert!(dma.blocks(0xC000));
+        assert!(dma.blocks(0x0000));
+        assert!(dma.blocks(0xFE10));
+        assert!(!dma.blocks(HIGH_RAM.begin));
+        assert!(!dma.blocks(IO_DMA));
+
+        for index in 1..DMA_LENGTH {
+            assert_eq!(dma.tick(), Some((0xC100 + index, 0xFE00 + index)));
+        }
+        assert!(!dma.blocks(0xC000));
+        assert_eq!(dma.tick(), None);
+    }
+
+    #[test]
+    fn test_restart() {
+        let mut dma = Dma::default();
+        dma.request(0x80);
+        for _ in 0..=10 {
+            dma.tick();
+        }
+
+        // The old transfer keeps going during the new one's setup, then it starts over
+        dma.request(0xD0);
+        assert_eq!(dma.tick(), Some((0x800A, 0xFE0A)));
+        assert!(dma.blocks(0xC000));
+        assert_eq!(dma.tick(), Some((0xD000, 0xFE00)));
+        for _ in 1..DMA_LENGTH {
+            dma.tick();
+        }
+        assert_eq!(dma.tick(), None);
+    }
+
+    #[test]
+    fn test_high_source() {
+        let mut dma = Dma::default();
+        dma.request(0xFE);
+        dma.tick();
+        assert_eq!(dma.tick(), Some((0xDE00, 0xFE00)));
+
+        // Echo RAM is read as is, and mirrors WRAM by itself
+        dma.request(0xE0);
+        dma.tick();
+        assert_eq!(dma.tick(), Some((0xE000, 0xFE00)));
+    }
+}
@@ -14,7 +14,7 @@ use crate::{
 mod decode;
 mod execute;
 mod instructions;
-mod interrupts;
+pub mod interrupts;
 mod optable;
 
 const Z_FLAG_MASK: u8 = 0x80;
@@ -7,11 +7,11 @@ use crate::{
     },
 };
 
-const VBLANK: u8 = 0x1;
-const STAT: u8 = 0x2;
-const TIMER: u8 = 0x4;
-const SERIAL: u8 = 0x8;
-const JOYPAD: u8 = 0x10;
+pub const VBLANK: u8 = 0x1;
+pub const STAT: u8 = 0x2;
+pub const TIMER: u8 = 0x4;
+pub const SERIAL: u8 = 0x8;
+pub const JOYPAD: u8 = 0x10;
 
 const VBLANK_HANDLER_ADDRESS: u16 = 0x40;
 const STAT_HANDLER_ADDRESS: u16 = 0x48;
@@ -28,6 +28,11 @@ const INT_ORDER: [(u8, u16, &str); 5] = [
 ];
 
 impl Processor {
+    /// Sets an interrupt's bit in IF, for the hardware that raises it.
+    pub fn request_interrupt(ctx: &mut GameBoy, int_mask: u8) {
+        Memory::write_masked(ctx, IO_IF, int_mask, int_mask);
+    }
+
     pub fn maybe_interrupt(ctx: &mut GameBoy) -> bool {
         if ctx.cpu.ime {
             let pending = Processor::pending_interrupts(ctx);
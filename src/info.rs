@@ -0,0 +1,215 @@
+use crate::{
+    byte_fmt,
+    gb::hardware::cartridge::{
+        RAM_BANK_SIZE, ROM_BANK_SIZE, archive,
+        header::{CartridgeHeader, CgbSupport, Destination},
+    },
+    unwrap_or_log,
+};
+use std::{fmt::Write, path::Path};
+
+pub const INFO_COMMAND: &str = "info";
+
+/// Prints everything in a ROM's header without running it.
+pub fn print_info(rom_path: &str, archive_entry: Option<&str>, json: bool) {
+    let rom = unwrap_or_log!(archive::read_rom_file(Path::new(rom_path), archive_entry));
+    let header = CartridgeHeader::parse(&rom);
+    if json {
+        println!("{}", format_json(&header));
+    } else {
+        print!("{}", format_text(&header));
+    }
+}
+
+fn format_text(header: &CartridgeHeader) -> String {
+    let mut out = String::new();
+    macro_rules! line {
+        ($name:expr, $($arg:tt)*) => {
+            writeln!(out, "{:<17}{}", concat!($name, ":"), format!($($arg)*)).unwrap()
+        };
+    }
+
+    line!("Title", "{}", header.title);
+    if let Some(code) = &header.manufacturer_code {
+        line!("Manufacturer", "{code}");
+    }
+    line!(
+        "Mapper",
+        "{} ({})",
+        header.cart_type_name().unwrap_or("Unknown"),
+        byte_fmt!(header.cart_type)
+    );
+    line!("ROM size", "{}", rom_size_text(header));
+    line!("RAM size", "{}", ram_size_text(header));
+    line!("Licensee", "{}", licensee_text(header));
+    line!("CGB", "{}", cgb_text(header.cgb_support));
+    line!("SGB", "{}", if header.sgb_support { "Yes" } else { "No" });
+    line!("Destination", "{}", destination_text(header.destination));
+    line!("Version", "{}", header.version);
+    line!("Logo", "{}", valid_text(header.logo_valid));
+    line!(
+        "Header checksum",
+        "{} ({})",
+        valid_text(header.header_checksum_valid),
+        byte_fmt!(header.header_checksum)
+    );
+    line!(
+        "Global checksum",
+        "{} (${:0>4X})",
+        valid_text(header.global_checksum_valid),
+        header.global_checksum
+    );
+    for warning in &header.warnings {
+        line!("Warning", "{warning}");
+    }
+
+    out
+}
+
+fn format_json(header: &CartridgeHeader) -> String {
+    let optional_number = |n: Option<usize>| match n {
+        Some(n) => n.to_string(),
+        None => "null".to_string(),
+    };
+    let optional_string = |s: Option<&str>| match s {
+        Some(s) => json_string(s),
+        None => "null".to_string(),
+    };
+    let warnings: Vec<String> = header
+        .warnings
+        .iter()
+        .map(|w| json_string(&w.to_string()))
+        .collect();
+
+    let fields = [
+        ("title", json_string(&header.title)),
+        (
+            "manufacturer_code",
+            optional_string(header.manufacturer_code.as_deref()),
+        ),
+        ("cart_type", header.cart_type.to_string()),
+        ("mapper", optional_string(header.cart_type_name())),
+        (
+            "rom_size",
+            optional_number(header.rom_banks.map(|b| b * ROM_BANK_SIZE)),
+        ),
+        ("rom_banks", optional_number(header.rom_banks)),
+        ("ram_size", optional_number(header.ram_size)),
+        ("old_licensee_code", header.old_licensee_code.to_string()),
+        ("new_licensee_code", json_string(&header.new_licensee_code)),
+        ("licensee", optional_string(header.licensee_name())),
+        ("cgb", json_string(cgb_text(header.cgb_support))),
+        ("sgb", header.sgb_support.to_string()),
+        (
+            "destination",
+            json_string(destination_text(header.destination)),
+        ),
+        ("version", header.version.to_string()),
+        ("logo_valid", header.logo_valid.to_string()),
+        ("header_checksum", header.header_checksum.to_string()),
+        (
+            "header_checksum_valid",
+            header.header_checksum_valid.to_string(),
+        ),
+        ("global_checksum", header.global_checksum.to_string()),
+        (
+            "global_checksum_valid",
+            header.global_checksum_valid.to_string(),
+        ),
+        ("warnings", format!("[{}]", warnings.join(","))),
+    ];
+
+    let body: Vec<String> = fields
+        .iter()
+        .map(|(name, value)| format!("{}:{value}", json_string(name)))
+        .collect();
+    format!("{{{}}}", body.join(","))
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:0>4x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn rom_size_text(header: &CartridgeHeader) -> String {
+    match header.rom_banks {
+        Some(banks) => format!("{} KiB ({banks} banks)", banks * ROM_BANK_SIZE / 1024),
+        None => format!("Unknown ({})", byte_fmt!(header.rom_size_code)),
+    }
+}
+
+fn ram_size_text(header: &CartridgeHeader) -> String {
+    match header.ram_size {
+        Some(0) => "None".to_string(),
+        Some(size) => format!("{} KiB ({} banks)", size / 1024, size / RAM_BANK_SIZE),
+        None => format!("Unknown ({})", byte_fmt!(header.ram_size_code)),
+    }
+}
+
+fn licensee_text(header: &CartridgeHeader) -> String {
+    let name = header.licensee_name().unwrap_or("Unknown");
+    if header.uses_new_licensee() {
+        format!("{name} (\"{}\")", header.new_licensee_code)
+    } else {
+        format!("{name} ({})", byte_fmt!(header.old_licensee_code))
+    }
+}
+
+fn cgb_text(cgb: CgbSupport) -> &'static str {
+    match cgb {
+        CgbSupport::None => "No",
+        CgbSupport::Compatible => "Compatible",
+        CgbSupport::Only => "Only",
+    }
+}
+
+fn destination_text(destination: Destination) -> &'static str {
+    match destination {
+        Destination::Japan => "Japan",
+        Destination::Overseas => "Overseas",
+    }
+}
+
+fn valid_text(valid: bool) -> &'static str {
+    if valid { "OK" } else { "BAD" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use test_log::test;
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(json_string("a\"b\\c"), "\"a\\\"b\\\\c\"");
+        assert_eq!(json_string("\n"), "\"\\u000a\"");
+    }
+
+    #[test]
+    fn test_format() {
+        let rom = fs::read("res/dummy_cartromonly_read_test.bin").unwrap();
+        let header = CartridgeHeader::parse(&rom);
+
+        let text = format_text(&header);
+        assert!(text.contains("Mapper:          MBC3 ($11)"));
+        assert!(text.contains("ROM size:        Unknown ($22)"));
+        assert!(text.contains("Logo:            BAD"));
+
+        let json = format_json(&header);
+        assert!(json.starts_with("{\"title\":\"\","));
+        assert!(json.contains("\"cart_type\":17,\"mapper\":\"MBC3\",\"rom_size\":null,"));
+        assert!(json.contains("\"logo_valid\":false"));
+        assert!(json.ends_with("\"]}"));
+    }
+}
@@ -2,7 +2,8 @@
 
 use crate::{
     gb::GameBoy,
-    options::{ALL_SIMPLE_OPTIONS, HELP},
+    info::{INFO_COMMAND, print_info},
+    options::{ALL_MULTI_OPTIONS, ALL_SIMPLE_OPTIONS, ALL_VALUE_OPTIONS, HELP, JSON, ROM_ENTRY},
 };
 use ftail::Ftail;
 use getopts::Options;
@@ -10,6 +11,7 @@ use log::{LevelFilter, debug, error};
 use std::{env, fs, panic, path::Path};
 
 mod gb;
+mod info;
 mod options;
 
 fn main() {
@@ -34,6 +36,12 @@ fn main() {
     for odef in ALL_SIMPLE_OPTIONS {
         opts.optflag(odef.short_name, odef.long_name, odef.desc);
     }
+    for odef in ALL_VALUE_OPTIONS {
+        opts.optopt(odef.short_name, odef.long_name, odef.desc, odef.hint);
+    }
+    for odef in ALL_MULTI_OPTIONS {
+        opts.optmulti(odef.short_name, odef.long_name, odef.desc, odef.hint);
+    }
 
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
@@ -43,12 +51,30 @@ fn main() {
     if has_opt!(matches, HELP) {
         print!(
             "{}",
-            opts.usage(&format!("Usage: gbemu [options] ROM_FILE"))
+            opts.usage(&format!(
+                "Usage: gbemu [options] ROM_FILE\n       gbemu {INFO_COMMAND} [--json] ROM_FILE"
+            ))
         );
         return;
     }
 
-    GameBoy::new(matches).run();
+    // Print the cartridge header instead of running it
+    if matches.free.first().map(String::as_str) == Some(INFO_COMMAND) {
+        match matches.free.get(1) {
+            Some(rom_path) => print_info(
+                rom_path,
+                get_opt!(matches, ROM_ENTRY).as_deref(),
+                has_opt!(matches, JSON),
+            ),
+            None => error_panic!("No ROM file provided."),
+        }
+        return;
+    }
+
+    match GameBoy::new(matches) {
+        Ok(mut gb) => gb.run(),
+        Err(e) => error_panic!("{e}"),
+    }
 }
 
 fn init_logging(base_dir: &str) {
@@ -4,6 +4,13 @@ pub struct SimpleOptionDef {
     pub desc: &'static str,
 }
 
+pub struct ValueOptionDef {
+    pub short_name: &'static str,
+    pub long_name: &'static str,
+    pub desc: &'static str,
+    pub hint: &'static str,
+}
+
 macro_rules! simple_options {
     ($($name:ident, $short:expr, $long:expr, $desc:expr;)*) => {
         $(pub const $name: SimpleOptionDef = SimpleOptionDef {
@@ -16,10 +23,50 @@ macro_rules! simple_options {
     };
 }
 
+macro_rules! value_options {
+    ($($name:ident, $short:expr, $long:expr, $hint:expr, $desc:expr;)*) => {
+        $(pub const $name: ValueOptionDef = ValueOptionDef {
+            short_name: $short,
+            long_name: $long,
+            desc: $desc,
+            hint: $hint,
+        };)*
+
+        pub const ALL_VALUE_OPTIONS: &[ValueOptionDef] = &[$($name),*];
+    };
+}
+
+macro_rules! multi_options {
+    ($($name:ident, $short:expr, $long:expr, $hint:expr, $desc:expr;)*) => {
+        $(pub const $name: ValueOptionDef = ValueOptionDef {
+            short_name: $short,
+            long_name: $long,
+            desc: $desc,
+            hint: $hint,
+        };)*
+
+        pub const ALL_MULTI_OPTIONS: &[ValueOptionDef] = &[$($name),*];
+    };
+}
+
 simple_options!(
-    HELP,      "h", "help",    "Show this help menu.";
-    META_INST, "m", "meta",    "Enable meta-instructions.";
-    DO_BOOT,   "b", "do-boot", "If set, runs the boot ROM before cartridge ROM. Skips the boot ROM otherwise.";
+    HELP,       "h", "help",       "Show this help menu.";
+    META_INST,  "m", "meta",       "Enable meta-instructions.";
+    DO_BOOT,    "b", "do-boot",    "If set, runs the boot ROM before cartridge ROM. Skips the boot ROM otherwise.";
+    RTC_FREEZE, "r", "rtc-freeze", "Restore the cartridge clock exactly as saved, instead of catching it up to the real time.";
+    JSON,       "j", "json",       "With the info command, print the cartridge header as JSON.";
+    PIXEL_FIFO, "f", "fifo",       "Draw with a dot-by-dot pixel FIFO, for demos and test ROMs that depend on mode 3 timing. Slower than the default scanline renderer.";
+);
+
+value_options!(
+    SAVE_FILE,    "s", "save",   "FILE", "Battery save file to use. Defaults to the ROM file with a .sav extension.";
+    ROM_ENTRY,    "e", "entry",  "NAME", "File to load from a zip archive. Defaults to the first .gb or .gbc file in it.";
+    CAMERA_IMAGE, "c", "camera", "FILE", "PGM or PPM image for the Game Boy Camera to see. Defaults to a test pattern.";
+    TILT,         "t", "tilt",   "X,Y",  "Tilt in g for carts with an accelerometer, or a file with one X,Y reading per frame. Defaults to lying flat.";
+);
+
+multi_options!(
+    PATCH, "p", "patch", "FILE", "IPS, UPS or BPS patch to apply to the ROM; can be repeated. Defaults to a patch with the ROM's name.";
 );
 
 #[macro_export]
@@ -28,3 +75,17 @@ macro_rules! has_opt {
         $matches.opt_present($op.long_name)
     };
 }
+
+#[macro_export]
+macro_rules! get_opt {
+    ($matches:expr, $op:ident) => {
+        $matches.opt_str($op.long_name)
+    };
+}
+
+#[macro_export]
+macro_rules! get_opts {
+    ($matches:expr, $op:ident) => {
+        $matches.opt_strs($op.long_name)
+    };
+}